@@ -0,0 +1,346 @@
+use std::fs;
+
+use crate::SpriteLoader::Playback;
+
+#[derive(Debug)]
+pub enum FrameSource {
+    Files, //Each frame names an image file
+    Sheet{image: String, frameWidth: u32, frameHeight: u32}, //Frames are cell numbers of a grid
+    Manifest(String), //Frames are names in a JSON atlas manifest
+}
+
+#[derive(Debug)]
+pub struct FrameDef {
+    pub image: String,
+    pub duration: u32, //In ticks
+    pub events: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum AnimationDef {
+    Standard{name: String, playback: Playback, source: FrameSource, frames: Vec<FrameDef>},
+    Flip{name: String, source: String}, //Plays the source animation mirrored horizontally
+}
+
+#[derive(Debug)]
+pub enum Condition {
+    Equals(String, String), //parameter=value
+    NotEquals(String, String), //parameter!=value
+    Finished, //The state's animation has played through
+}
+
+#[derive(Debug)]
+pub struct TransitionDef {
+    pub target: String,
+    pub conditions: Vec<Condition>,
+}
+
+//The animation's name can use parameters, float_{direction} plays float_up while direction is up
+#[derive(Debug)]
+pub struct StateDef {
+    pub name: String,
+    pub animation: String,
+    pub transitions: Vec<TransitionDef>,
+}
+
+#[derive(Debug)]
+pub struct ParameterDef {
+    pub name: String,
+    pub values: Vec<String>, //The first is the starting value
+}
+
+#[derive(Debug)]
+pub struct AnimationSetData {
+    pub animations: Vec<AnimationDef>,
+    pub parameters: Vec<ParameterDef>,
+    pub states: Vec<StateDef>, //The first one is where the state machine starts
+}
+
+impl AnimationDef {
+    pub fn name(&self) -> &str {
+        match self {
+            AnimationDef::Standard{name, ..} | AnimationDef::Flip{name, ..} => name,
+        }
+    }
+}
+
+pub fn load(filename: &str) -> Result<AnimationSetData, String> {
+    let source = fs::read_to_string(filename)
+        .map_err(|e| format!("{}: could not read animation file: {}", filename, e))?;
+    parse(&source).map_err(|e| format!("{}: {}", filename, e))
+}
+
+pub fn parse(source: &str) -> Result<AnimationSetData, String> {
+    let mut animations: Vec<AnimationDef> = vec![];
+    let mut parameters: Vec<ParameterDef> = vec![];
+    let mut states: Vec<StateDef> = vec![];
+
+    for (lineNumber, line) in source.lines().enumerate() {
+        let lineNumber = lineNumber + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        match words[0] {
+            "animation" => {
+                if words.len() != 3 {
+                    return Err(format!("line {}: expected 'animation <name> <once|loop|pingpong|holdlast>'", lineNumber));
+                }
+                checkUnique(&animations, words[1]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let playback = parsePlayback(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                animations.push(AnimationDef::Standard{name: words[1].to_string(), playback, source: FrameSource::Files, frames: vec![]});
+            },
+            "flip" => {
+                if words.len() != 3 {
+                    return Err(format!("line {}: expected 'flip <name> <animation>'", lineNumber));
+                }
+                checkUnique(&animations, words[1]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                match animations.iter().find(|animation| animation.name() == words[2]) {
+                    Some(AnimationDef::Standard{..}) => (),
+                    Some(AnimationDef::Flip{..}) => return Err(format!("line {}: '{}' is already a flip", lineNumber, words[2])),
+                    None => return Err(format!("line {}: no animation named '{}' above", lineNumber, words[2])),
+                }
+                animations.push(AnimationDef::Flip{name: words[1].to_string(), source: words[2].to_string()});
+            },
+            "sheet" => {
+                let sheet = match words[1..] {
+                    [manifest] if manifest.ends_with(".json") => FrameSource::Manifest(manifest.to_string()),
+                    [image, width, height] => FrameSource::Sheet{
+                        image: image.to_string(),
+                        frameWidth: parseNumber(width).map_err(|e| format!("line {}: {}", lineNumber, e))?,
+                        frameHeight: parseNumber(height).map_err(|e| format!("line {}: {}", lineNumber, e))?,
+                    },
+                    _ => return Err(format!("line {}: expected 'sheet <manifest.json>' or 'sheet <image> <frame width> <frame height>'", lineNumber)),
+                };
+                match animations.last_mut() {
+                    Some(AnimationDef::Standard{source, frames, ..}) if frames.is_empty() => *source = sheet,
+                    _ => return Err(format!("line {}: a sheet must come right after its animation", lineNumber)),
+                }
+            },
+            "frame" => {
+                if words.len() < 3 {
+                    return Err(format!("line {}: expected 'frame <image> <ticks> [events]'", lineNumber));
+                }
+                let duration = parseNumber(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let events = words[3..].iter().map(|event| event.to_string()).collect();
+                match animations.last_mut() {
+                    Some(AnimationDef::Standard{frames, ..}) => frames.push(FrameDef{image: words[1].to_string(), duration, events}),
+                    _ => return Err(format!("line {}: frames must follow the animation they belong to", lineNumber)),
+                }
+            },
+            "parameter" => {
+                if words.len() < 3 {
+                    return Err(format!("line {}: expected 'parameter <name> <value>...'", lineNumber));
+                }
+                if parameters.iter().any(|parameter| parameter.name == words[1]) {
+                    return Err(format!("line {}: parameter '{}' is defined twice", lineNumber, words[1]));
+                }
+                let values = words[2..].iter().map(|value| value.to_string()).collect();
+                parameters.push(ParameterDef{name: words[1].to_string(), values});
+            },
+            "state" => {
+                if words.len() != 3 {
+                    return Err(format!("line {}: expected 'state <name> <animation>'", lineNumber));
+                }
+                if states.iter().any(|state| state.name == words[1]) {
+                    return Err(format!("line {}: state '{}' is defined twice", lineNumber, words[1]));
+                }
+                states.push(StateDef{name: words[1].to_string(), animation: words[2].to_string(), transitions: vec![]});
+            },
+            "transition" => {
+                if words.len() < 4 {
+                    return Err(format!("line {}: expected 'transition <from> <to> <conditions>'", lineNumber));
+                }
+                let mut conditions = vec![];
+                for word in &words[3..] {
+                    conditions.push(parseCondition(word).map_err(|e| format!("line {}: {}", lineNumber, e))?);
+                }
+                match states.iter_mut().find(|state| state.name == words[1]) {
+                    Some(state) => state.transitions.push(TransitionDef{target: words[2].to_string(), conditions}),
+                    None => return Err(format!("line {}: no state named '{}' above", lineNumber, words[1])),
+                }
+            },
+            other => return Err(format!("line {}: unknown directive '{}'", lineNumber, other)),
+        }
+    }
+
+    if animations.is_empty() {
+        return Err("no animations".to_string());
+    }
+    for animation in animations.iter() {
+        if let AnimationDef::Standard{name, frames, ..} = animation {
+            if frames.is_empty() {
+                return Err(format!("animation '{}' has no frames", name));
+            }
+        }
+    }
+    for state in states.iter() {
+        //Every value the parameters can take has to name an animation
+        for name in expandTemplate(&state.animation, &parameters).map_err(|e| format!("state '{}': {}", state.name, e))? {
+            if !animations.iter().any(|animation| animation.name() == name) {
+                return Err(format!("state '{}' plays missing animation '{}'", state.name, name));
+            }
+        }
+        for transition in state.transitions.iter() {
+            if !states.iter().any(|other| other.name == transition.target) {
+                return Err(format!("state '{}' has a transition to missing state '{}'", state.name, transition.target));
+            }
+            for condition in transition.conditions.iter() {
+                if let Condition::Equals(name, value) | Condition::NotEquals(name, value) = condition {
+                    checkValue(&parameters, name, value).map_err(|e| format!("state '{}': {}", state.name, e))?;
+                }
+            }
+        }
+    }
+    Ok(AnimationSetData{animations, parameters, states})
+}
+
+//Every animation name the template can turn into, one for each combination of its parameters' values
+fn expandTemplate(template: &str, parameters: &[ParameterDef]) -> Result<Vec<String>, String> {
+    let start = match template.find('{') {
+        Some(start) => start,
+        None => return Ok(vec![template.to_string()]),
+    };
+    let end = match template[start..].find('}') {
+        Some(end) => start + end,
+        None => return Err(format!("'{}' has a '{{' without a '}}'", template)),
+    };
+    let name = &template[start + 1..end];
+    let parameter = parameters.iter().find(|parameter| parameter.name == name)
+        .ok_or_else(|| format!("no parameter named '{}'", name))?;
+    let mut names = vec![];
+    for value in parameter.values.iter() {
+        names.extend(expandTemplate(&format!("{}{}{}", &template[..start], value, &template[end + 1..]), parameters)?);
+    }
+    Ok(names)
+}
+
+fn checkValue(parameters: &[ParameterDef], name: &str, value: &str) -> Result<(), String> {
+    match parameters.iter().find(|parameter| parameter.name == name) {
+        Some(parameter) if parameter.values.iter().any(|other| other == value) => Ok(()),
+        Some(_) => Err(format!("parameter '{}' can't be '{}'", name, value)),
+        None => Err(format!("no parameter named '{}'", name)),
+    }
+}
+
+fn checkUnique(animations: &[AnimationDef], name: &str) -> Result<(), String> {
+    if animations.iter().any(|animation| animation.name() == name) {
+        return Err(format!("animation '{}' is defined twice", name));
+    }
+    Ok(())
+}
+
+fn parseCondition(word: &str) -> Result<Condition, String> {
+    if word == "finished" {
+        return Ok(Condition::Finished);
+    }
+    if let Some(split) = word.find("!=") {
+        return Ok(Condition::NotEquals(word[..split].to_string(), word[split + 2..].to_string()));
+    }
+    if let Some(split) = word.find('=') {
+        return Ok(Condition::Equals(word[..split].to_string(), word[split + 1..].to_string()));
+    }
+    Err(format!("condition '{}' should be finished, <parameter>=<value> or <parameter>!=<value>", word))
+}
+
+fn parsePlayback(word: &str) -> Result<Playback, String> {
+    match word {
+        "once" => Ok(Playback::Once),
+        "loop" => Ok(Playback::Loop),
+        "pingpong" => Ok(Playback::PingPong),
+        "holdlast" => Ok(Playback::HoldLast),
+        other => Err(format!("unknown playback '{}'", other)),
+    }
+}
+
+fn parseNumber(word: &str) -> Result<u32, String> {
+    match word.parse::<u32>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("'{}' is not a positive number", word)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANIMATIONS: &str = "animation float_down loop\nframe down.png 10\nanimation float_up loop\nframe up.png 10\n\
+        animation attack_down once\nframe attack.png 5 hit\nflip attack_up attack_down\n";
+
+    #[test]
+    fn statesWithParameters() {
+        let source = format!("{}parameter direction down up\nparameter attacking false true\n\
+            state idle float_{{direction}}\nstate attack attack_{{direction}}\n\
+            transition idle attack attacking=true\ntransition attack idle finished\n", ANIMATIONS);
+        let set = parse(&source).unwrap();
+        assert_eq!(set.animations.len(), 4);
+        assert_eq!(set.parameters[0].values, vec!["down", "up"]);
+        assert_eq!(set.states[1].transitions[0].target, "idle");
+        assert_eq!(set.animations[0].name(), "float_down");
+        match &set.animations[2] {
+            AnimationDef::Standard{frames, ..} => assert_eq!(frames[0].events, vec!["hit"]),
+            AnimationDef::Flip{..} => panic!("expected a standard animation"),
+        }
+    }
+
+    #[test]
+    fn ninjaAnimations() {
+        let set = load("Resources/Animations/Ninja.anim").unwrap();
+        assert_eq!(set.states[0].name, "idle");
+    }
+
+    #[test]
+    fn misspelledParameter() {
+        let source = format!("{}parameter direction down up\nstate idle float_{{dirction}}\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle': no parameter named 'dirction'");
+    }
+
+    #[test]
+    fn parameterValueWithoutAnimation() {
+        let source = format!("{}parameter direction down up left\nstate idle float_{{direction}}\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle' plays missing animation 'float_left'");
+    }
+
+    #[test]
+    fn missingAnimation() {
+        let source = format!("{}state idle flaot_down\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle' plays missing animation 'flaot_down'");
+    }
+
+    #[test]
+    fn unclosedPlaceholder() {
+        let source = format!("{}parameter direction down up\nstate idle float_{{direction\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle': 'float_{direction' has a '{' without a '}'");
+    }
+
+    #[test]
+    fn conditionsUseDeclaredValues() {
+        let source = format!("{}parameter attacking false true\nstate idle float_down\nstate attack attack_down\n\
+            transition idle attack attacking=yes\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle': parameter 'attacking' can't be 'yes'");
+        let source = format!("{}state idle float_down\nstate attack attack_down\ntransition idle attack hurt!=true\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle': no parameter named 'hurt'");
+    }
+
+    #[test]
+    fn transitionToMissingState() {
+        let source = format!("{}state idle float_down\ntransition idle attack finished\n", ANIMATIONS);
+        assert_eq!(parse(&source).unwrap_err(), "state 'idle' has a transition to missing state 'attack'");
+    }
+
+    #[test]
+    fn malformedAnimations() {
+        assert_eq!(parse("animation a loop\n").unwrap_err(), "animation 'a' has no frames");
+        assert_eq!(parse("frame a.png 5\n").unwrap_err(), "line 1: frames must follow the animation they belong to");
+        assert_eq!(parse("animation a loop\nframe a.png 0\n").unwrap_err(), "line 2: '0' is not a positive number");
+        assert_eq!(parse("animation a forever\n").unwrap_err(), "line 1: unknown playback 'forever'");
+        assert_eq!(parse("flip b a\n").unwrap_err(), "line 1: no animation named 'a' above");
+        assert_eq!(parse(&format!("{}parameter direction up\nparameter direction down\n", ANIMATIONS)).unwrap_err(),
+            "line 9: parameter 'direction' is defined twice");
+    }
+}
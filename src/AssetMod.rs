@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use sdl2::image::LoadTexture;
+use sdl2::mixer::{Chunk, Music};
+use sdl2::render::{Texture, TextureCreator};
+
+use serde_json::Value;
+
+use crate::SpriteLoader::{self, Symmetry};
+
+//Loads the image at a path into a texture from the game's texture creator
+type TextureLoader<'a> = Box<dyn Fn(&str) -> Result<Texture<'a>, String> + 'a>;
+
+//Every asset is loaded once per path and handed out as a shared handle.
+//The cache keeps its own handle, so an asset stays loaded until unloadUnused finds nothing else holding it.
+//Textures sit behind a RefCell so reloadChanged can replace them in place.
+pub struct Assets<'a> {
+    loadTexture: TextureLoader<'a>,
+    textures: RefCell<HashMap<String, Rc<RefCell<Texture<'a>>>>>,
+    modified: RefCell<HashMap<String, Option<SystemTime>>>,
+    chunks: RefCell<HashMap<String, Rc<Chunk>>>,
+    music: RefCell<HashMap<String, Rc<Music<'static>>>>,
+    symmetries: HashMap<String, Symmetry>,
+}
+
+impl<'a> Assets<'a> {
+    //Textures are made by creator, which can belong to a window or to a software renderer
+    pub fn new<T: 'a>(creator: &'a TextureCreator<T>) -> Assets<'a> {
+        let loadTexture = Box::new(move |path: &str| creator.load_texture(path));
+        Assets{loadTexture, textures: RefCell::new(HashMap::new()), modified: RefCell::new(HashMap::new()), chunks: RefCell::new(HashMap::new()), music: RefCell::new(HashMap::new()),
+            symmetries: HashMap::new()}
+    }
+
+    //Reads the metadata of the images next to a JSON manifest:
+    //{"<image>": {"symmetry": "none|horizontal|vertical|quad"}, ...}
+    pub fn loadManifest(&mut self, filename: &str) -> Result<(), String> {
+        let source = fs::read_to_string(filename)
+            .map_err(|e| format!("{}: could not read manifest: {}", filename, e))?;
+        let manifest: Value = serde_json::from_str(&source)
+            .map_err(|e| format!("{}: invalid JSON: {}", filename, e))?;
+        let images = manifest.as_object()
+            .ok_or_else(|| format!("{}: the manifest should map image names to their metadata", filename))?;
+
+        let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+        for (image, metadata) in images.iter() {
+            if let Some(symmetry) = metadata["symmetry"].as_str() {
+                let symmetry = SpriteLoader::parseSymmetry(symmetry).map_err(|e| format!("{}: {}: {}", filename, image, e))?;
+                self.symmetries.insert(resolve(directory, image), symmetry);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn symmetry(&self, path: &str) -> Symmetry {
+        self.symmetries.get(&normalizePath(path)).copied().unwrap_or(Symmetry::None)
+    }
+
+    pub fn texture(&self, path: &str) -> Result<Rc<RefCell<Texture<'a>>>, String> {
+        let path = &normalizePath(path);
+        getOrLoad(&self.textures, path, || {
+            self.modified.borrow_mut().insert(path.to_string(), modifiedTime(path));
+            (self.loadTexture)(path).map(RefCell::new)
+        })
+    }
+
+    pub fn chunk(&self, path: &str) -> Result<Rc<Chunk>, String> {
+        let path = &normalizePath(path);
+        getOrLoad(&self.chunks, path, || Chunk::from_file(path))
+    }
+
+    pub fn music(&self, path: &str) -> Result<Rc<Music<'static>>, String> {
+        let path = &normalizePath(path);
+        getOrLoad(&self.music, path, || Music::from_file(path))
+    }
+
+    //How many handles to the asset are held outside the cache
+    pub fn references(&self, path: &str) -> usize {
+        let path = &normalizePath(path);
+        let count = self.textures.borrow().get(path).map(Rc::strong_count)
+            .or_else(|| self.chunks.borrow().get(path).map(Rc::strong_count))
+            .or_else(|| self.music.borrow().get(path).map(Rc::strong_count));
+        count.map_or(0, |count| count - 1)
+    }
+
+    //Drops the cache's own handle to the asset. Whatever still holds a handle keeps it working,
+    //and the asset is freed once the last of those is dropped. Loading the path again reads the file again.
+    pub fn unload(&self, path: &str) {
+        let path = &normalizePath(path);
+        self.textures.borrow_mut().remove(path);
+        self.modified.borrow_mut().remove(path);
+        self.chunks.borrow_mut().remove(path);
+        self.music.borrow_mut().remove(path);
+    }
+
+    //Frees every asset nothing outside the cache is using
+    pub fn unloadUnused(&self) {
+        self.textures.borrow_mut().retain(|_, texture| Rc::strong_count(texture) > 1);
+        let textures = self.textures.borrow();
+        self.modified.borrow_mut().retain(|path, _| textures.contains_key(path));
+        self.chunks.borrow_mut().retain(|_, chunk| Rc::strong_count(chunk) > 1);
+        self.music.borrow_mut().retain(|_, music| Rc::strong_count(music) > 1);
+    }
+
+    //Reloads every texture whose file changed since it was loaded, every sprite using it picks up the new image.
+    //Returns the paths that were reloaded and the errors of those that failed, which keep their old texture.
+    pub fn reloadChanged(&self) -> (Vec<String>, Vec<String>) {
+        let mut reloaded = vec![];
+        let mut errors = vec![];
+        for (path, texture) in self.textures.borrow().iter() {
+            let modified = modifiedTime(path);
+            if self.modified.borrow().get(path) == Some(&modified) {
+                continue;
+            }
+            self.modified.borrow_mut().insert(path.clone(), modified);
+            match (self.loadTexture)(path) {
+                Ok(loaded) => {
+                    *texture.borrow_mut() = loaded;
+                    reloaded.push(path.clone());
+                },
+                Err(e) => errors.push(format!("{}: {}", path, e)),
+            }
+        }
+        (reloaded, errors)
+    }
+}
+
+//Collapses . and .. so a file gets the same key however a map or manifest reached it
+pub fn normalizePath(path: &str) -> String {
+    let mut components: Vec<Component> = vec![];
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match components.last() {
+                Some(Component::Normal(_)) => {components.pop();},
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => components.push(component),
+            },
+            _ => components.push(component),
+        }
+    }
+    components.iter().collect::<PathBuf>().to_string_lossy().into_owned()
+}
+
+//A path written in a file in directory, as a normalised path from the working directory
+pub fn resolve(directory: &Path, path: &str) -> String {
+    normalizePath(&directory.join(path).to_string_lossy())
+}
+
+//None when the file can't be read, so a deleted file counts as a change once it comes back
+pub fn modifiedTime(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn getOrLoad<T, F>(cache: &RefCell<HashMap<String, Rc<T>>>, path: &str, load: F) -> Result<Rc<T>, String>
+where F: FnOnce() -> Result<T, String> {
+    if let Some(asset) = cache.borrow().get(path) {
+        return Ok(Rc::clone(asset));
+    }
+    let asset = Rc::new(load().map_err(|e| format!("{}: {}", path, e))?);
+    cache.borrow_mut().insert(path.to_string(), Rc::clone(&asset));
+    Ok(asset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizedPaths() {
+        assert_eq!(normalizePath("Resources/Maps/../Images/Ground.png"), "Resources/Images/Ground.png");
+        assert_eq!(normalizePath("./Resources/./Images/Ground.png"), "Resources/Images/Ground.png");
+        assert_eq!(normalizePath("Resources/Images/Ground.png"), "Resources/Images/Ground.png");
+        assert_eq!(normalizePath("../Images/Ground.png"), "../Images/Ground.png");
+        assert_eq!(normalizePath("Maps/../../Images/Ground.png"), "../Images/Ground.png");
+        assert_eq!(normalizePath("/../Images/Ground.png"), "/Images/Ground.png");
+    }
+
+    #[test]
+    fn resolvedPaths() {
+        assert_eq!(resolve(Path::new("Resources/Maps"), "../Images/Ground.png"), "Resources/Images/Ground.png");
+        assert_eq!(resolve(Path::new(""), "Resources/Maps/Level2.map"), "Resources/Maps/Level2.map");
+    }
+}
@@ -0,0 +1,81 @@
+use sdl2::rect::Rect;
+
+pub struct Camera {
+    view: Rect,
+    deadZone: Rect,
+    worldWidth: u32,
+    worldHeight: u32,
+}
+
+impl Camera {
+    pub fn new(width: u32, height: u32, worldWidth: u32, worldHeight: u32) -> Camera {
+        //The target can move freely inside the middle third of the screen before the camera scrolls
+        let deadZone = Rect::new(width as i32 / 3, height as i32 / 3, width / 3, height / 3);
+        let mut camera = Camera{view: Rect::new(0, 0, width, height), deadZone, worldWidth, worldHeight};
+        camera.clamp();
+        camera
+    }
+
+    pub fn setWorldSize(&mut self, worldWidth: u32, worldHeight: u32) {
+        self.worldWidth = worldWidth;
+        self.worldHeight = worldHeight;
+        self.clamp();
+    }
+
+    pub fn view(&self) -> Rect {
+        self.view
+    }
+
+    pub fn centerOn(&mut self, target: Rect) {
+        let center = target.center();
+        self.view.center_on(center);
+        self.clamp();
+    }
+
+    pub fn follow(&mut self, target: Rect) {
+        let zone = Rect::new(
+            self.view.x() + self.deadZone.x(),
+            self.view.y() + self.deadZone.y(),
+            self.deadZone.width(),
+            self.deadZone.height(),
+        );
+
+        let mut x = self.view.x();
+        let mut y = self.view.y();
+
+        if target.left() < zone.left() {
+            x -= zone.left() - target.left();
+        }
+        else if target.right() > zone.right() {
+            x += target.right() - zone.right();
+        }
+
+        if target.top() < zone.top() {
+            y -= zone.top() - target.top();
+        }
+        else if target.bottom() > zone.bottom() {
+            y += target.bottom() - zone.bottom();
+        }
+
+        self.view.reposition((x, y));
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        let x = clampAxis(self.view.x(), self.view.width(), self.worldWidth);
+        let y = clampAxis(self.view.y(), self.view.height(), self.worldHeight);
+        self.view.reposition((x, y));
+    }
+
+    pub fn toScreen(&self, world: Rect) -> Rect {
+        Rect::new(world.x() - self.view.x(), world.y() - self.view.y(), world.width(), world.height())
+    }
+}
+
+fn clampAxis(position: i32, viewSize: u32, worldSize: u32) -> i32 {
+    if worldSize <= viewSize {
+        //Maps smaller than the screen are centered instead of pinned to the corner
+        return -((viewSize - worldSize) as i32 / 2);
+    }
+    position.max(0).min((worldSize - viewSize) as i32)
+}
@@ -1,6 +1,194 @@
-use sdl2::rect::Rect;
+use sdl2::rect::{Point, Rect};
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    pub fn unit(&self) -> (i32, i32) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HitTarget {
+    Tile(i32, i32),
+    Entity(&'static str),
+}
+
+pub struct Overlap {
+    pub targets: Vec<HitTarget>,
+    pub penetration: (i32, i32), //Smallest offset that moves the hitbox out of everything it overlaps
+}
+
+pub struct RayHit {
+    pub target: HitTarget,
+    pub point: Point,
+    pub distance: f32,
+}
 
 pub trait Collision {
     fn doesCollide(&self, hitbox: Rect) -> bool;
+
+    fn overlap(&self, hitbox: Rect) -> Option<Overlap>;
+
+    fn containsPoint(&self, point: Point) -> bool {
+        self.doesCollide(Rect::new(point.x(), point.y(), 1, 1))
+    }
+
+    //Returns the first thing hit on the way from start to end, if anything
+    fn raycast(&self, start: Point, end: Point) -> Option<RayHit> {
+        let (dx, dy) = (end.x() - start.x(), end.y() - start.y());
+        let steps = dx.abs().max(dy.abs());
+        for step in 0..=steps {
+            let t = if steps == 0 {0f32} else {step as f32 / steps as f32};
+            let point = Point::new(
+                start.x() + (dx as f32 * t).round() as i32,
+                start.y() + (dy as f32 * t).round() as i32,
+            );
+            if let Some(overlap) = self.overlap(Rect::new(point.x(), point.y(), 1, 1)) {
+                return Some(RayHit{target: overlap.targets[0], point, distance: distance(start, point)});
+            }
+        }
+        None
+    }
+
+    //Returns how far (up to distance) the hitbox can travel in direction before touching something
+    fn sweep(&self, hitbox: Rect, direction: Direction, distance: u32) -> u32 {
+        let (x, y) = direction.unit();
+        for step in 1..=distance {
+            let mut moved = hitbox;
+            moved.offset(x * step as i32, y * step as i32);
+            if self.doesCollide(moved) {
+                return step - 1;
+            }
+        }
+        distance
+    }
+}
+
+pub struct Movement {
+    pub dx: i32,
+    pub dy: i32,
+    pub normal: (i32, i32), //Points away from whatever stopped the movement, (0, 0) if nothing did
+}
+
+//Moves along x then y, stopping each axis flush against the nearest obstacle so the other axis can still slide
+pub fn moveAndSlide(colliders: &[&dyn Collision], hitbox: Rect, dx: i32, dy: i32) -> Movement {
+    let mut hitbox = hitbox;
+    let mut movement = Movement{dx: 0, dy: 0, normal: (0, 0)};
+
+    if dx != 0 {
+        let direction = if dx < 0 {Direction::Left} else {Direction::Right};
+        let allowed = sweepAll(colliders, hitbox, direction, dx.unsigned_abs());
+        movement.dx = allowed as i32 * dx.signum();
+        if allowed < dx.unsigned_abs() {
+            movement.normal.0 = -dx.signum();
+        }
+        hitbox.offset(movement.dx, 0);
+    }
+
+    if dy != 0 {
+        let direction = if dy < 0 {Direction::Up} else {Direction::Down};
+        let allowed = sweepAll(colliders, hitbox, direction, dy.unsigned_abs());
+        movement.dy = allowed as i32 * dy.signum();
+        if allowed < dy.unsigned_abs() {
+            movement.normal.1 = -dy.signum();
+        }
+    }
+
+    movement
+}
+
+fn sweepAll(colliders: &[&dyn Collision], hitbox: Rect, direction: Direction, distance: u32) -> u32 {
+    colliders.iter().fold(distance, |allowed, collider| collider.sweep(hitbox, direction, allowed))
+}
+
+pub fn hasLineOfSight(colliders: &[&dyn Collision], start: Point, end: Point) -> bool {
+    colliders.iter().all(|collider| collider.raycast(start, end).is_none())
+}
+
+pub fn penetration(hitbox: Rect, obstacle: Rect) -> (i32, i32) {
+    let pushLeft = obstacle.left() - hitbox.right();
+    let pushRight = obstacle.right() - hitbox.left();
+    let pushUp = obstacle.top() - hitbox.bottom();
+    let pushDown = obstacle.bottom() - hitbox.top();
+    let x = if -pushLeft < pushRight {pushLeft} else {pushRight};
+    let y = if -pushUp < pushDown {pushUp} else {pushDown};
+    if x.abs() < y.abs() {(x, 0)} else {(0, y)}
+}
+
+pub fn distance(a: Point, b: Point) -> f32 {
+    let (dx, dy) = ((b.x() - a.x()) as f32, (b.y() - a.y()) as f32);
+    (dx * dx + dy * dy).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Crate(Rect);
+
+    impl Collision for Crate {
+        fn doesCollide(&self, hitbox: Rect) -> bool {
+            self.0.has_intersection(hitbox)
+        }
+
+        fn overlap(&self, hitbox: Rect) -> Option<Overlap> {
+            if !self.doesCollide(hitbox) {
+                return None;
+            }
+            Some(Overlap{targets: vec![HitTarget::Entity("crate")], penetration: penetration(hitbox, self.0)})
+        }
+    }
+
+    #[test]
+    fn raycastStopsAtTheFirstPixelHit() {
+        let hit = Crate(Rect::new(10, 0, 5, 10)).raycast(Point::new(0, 5), Point::new(20, 5)).unwrap();
+        assert!(hit.target == HitTarget::Entity("crate"));
+        assert_eq!(hit.point, Point::new(10, 5));
+        assert_eq!(hit.distance, 10f32);
+    }
+
+    #[test]
+    fn raycastOfNoLength() {
+        let obstacle = Crate(Rect::new(10, 0, 5, 10));
+        assert!(obstacle.raycast(Point::new(0, 0), Point::new(0, 0)).is_none());
+        assert_eq!(obstacle.raycast(Point::new(12, 3), Point::new(12, 3)).unwrap().distance, 0f32);
+    }
+
+    #[test]
+    fn containsPoint() {
+        let obstacle = Crate(Rect::new(10, 0, 5, 10));
+        assert!(obstacle.containsPoint(Point::new(10, 0)));
+        assert!(!obstacle.containsPoint(Point::new(15, 0)));
+    }
+
+    #[test]
+    fn lineOfSight() {
+        let near = Crate(Rect::new(10, 0, 5, 10));
+        let far = Crate(Rect::new(40, 0, 5, 10));
+        let colliders: [&dyn Collision; 2] = [&near, &far];
+        assert!(hasLineOfSight(&colliders, Point::new(0, 20), Point::new(50, 20)));
+        assert!(!hasLineOfSight(&colliders, Point::new(20, 5), Point::new(50, 5)));
+        assert!(hasLineOfSight(&colliders, Point::new(0, 5), Point::new(5, 5)));
+    }
 }
@@ -0,0 +1,307 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::time::SystemTime;
+
+use sdl2::keyboard::{KeyboardState, Scancode};
+
+use crate::AssetMod;
+
+//What the player can ask for, entities check these instead of keys
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Attack,
+    Interact,
+    Pause,
+}
+
+//The names used in the controls file
+const ACTIONS: [(Action, &str); 7] = [
+    (Action::MoveUp, "up"),
+    (Action::MoveDown, "down"),
+    (Action::MoveLeft, "left"),
+    (Action::MoveRight, "right"),
+    (Action::Attack, "attack"),
+    (Action::Interact, "interact"),
+    (Action::Pause, "pause"),
+];
+
+//Keys are scancodes, so they stay in the same place on the keyboard whatever its layout
+#[derive(Debug)]
+pub struct Bindings {
+    keys: HashMap<Action, Vec<Scancode>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        let mut keys = HashMap::new();
+        keys.insert(Action::MoveUp, vec![Scancode::Up]);
+        keys.insert(Action::MoveDown, vec![Scancode::Down]);
+        keys.insert(Action::MoveLeft, vec![Scancode::Left]);
+        keys.insert(Action::MoveRight, vec![Scancode::Right]);
+        keys.insert(Action::Attack, vec![Scancode::Space, Scancode::KpSpace]);
+        keys.insert(Action::Interact, vec![Scancode::Return]);
+        keys.insert(Action::Pause, vec![Scancode::Escape, Scancode::P]);
+        Bindings{keys}
+    }
+}
+
+impl Bindings {
+    pub fn load(filename: &str) -> Result<Bindings, String> {
+        let source = fs::read_to_string(filename)
+            .map_err(|e| format!("{}: could not read controls file: {}", filename, e))?;
+        Bindings::parse(&source).map_err(|e| format!("{}: {}", filename, e))
+    }
+
+    //Each 'bind <action> <key>, <key>...' line replaces the action's default keys.
+    //Key names are SDL's, like Up, W, Space or Keypad Enter. Keys with a comma or a '#' in their
+    //name are quoted or escaped, like "," or \#, since a '#' after whitespace starts a comment.
+    pub fn parse(source: &str) -> Result<Bindings, String> {
+        let mut bindings = Bindings::default();
+
+        for (lineNumber, line) in source.lines().enumerate() {
+            let lineNumber = lineNumber + 1;
+            let (directive, rest) = firstWord(stripComment(line));
+            if directive.is_empty() {
+                continue;
+            }
+
+            match directive {
+                "bind" => {
+                    let (action, keys) = firstWord(rest);
+                    let keys = splitKeys(keys).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                    if keys.is_empty() {
+                        return Err(format!("line {}: expected 'bind <action> <key>, <key>...'", lineNumber));
+                    }
+                    let action = parseAction(action).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                    let keys = keys.iter()
+                        .map(|name| Scancode::from_name(name).ok_or_else(|| format!("line {}: unknown key '{}'", lineNumber, name)))
+                        .collect::<Result<Vec<Scancode>, String>>()?;
+                    bindings.rebind(action, &keys);
+                },
+                other => return Err(format!("line {}: unknown directive '{}'", lineNumber, other)),
+            }
+        }
+        Ok(bindings)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Scancode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    //Adds the key to the action, taking it away from whatever it did before
+    pub fn bind(&mut self, action: Action, key: Scancode) {
+        for keys in self.keys.values_mut() {
+            keys.retain(|bound| *bound != key);
+        }
+        self.keys.entry(action).or_default().push(key);
+    }
+
+    //Replaces the action's keys, taking them away from whatever they did before
+    pub fn rebind(&mut self, action: Action, keys: &[Scancode]) {
+        self.keys.insert(action, vec![]);
+        for key in keys {
+            self.bind(action, *key);
+        }
+    }
+}
+
+//A '#' starts a comment at the start of a line or after whitespace, unless it's quoted or escaped
+fn stripComment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut previous: Option<char> = None;
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        }
+        else if c == '\\' {
+            escaped = true;
+        }
+        else if c == '"' {
+            quoted = !quoted;
+        }
+        else if c == '#' && !quoted && previous.is_none_or(char::is_whitespace) {
+            return &line[..index];
+        }
+        previous = Some(c);
+    }
+    line
+}
+
+//Splits off the first word, leaving the rest of the text as it was
+fn firstWord(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], &text[end..]),
+        None => (text, ""),
+    }
+}
+
+//Key names can have spaces in them, so only the commas that aren't quoted or escaped separate keys.
+//Runs of whitespace inside a name count as one space.
+fn splitKeys(text: &str) -> Result<Vec<String>, String> {
+    if text.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let mut keys = vec![];
+    let mut key = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => key.push(chars.next().ok_or("nothing to escape at the end of the line")?),
+            '"' => quoted = !quoted,
+            ',' if !quoted => keys.push(std::mem::take(&mut key)),
+            _ => key.push(c),
+        }
+    }
+    if quoted {
+        return Err("unclosed quote".to_string());
+    }
+    keys.push(key);
+    Ok(keys.iter().map(|key| key.split_whitespace().collect::<Vec<&str>>().join(" ")).collect())
+}
+
+fn parseAction(word: &str) -> Result<Action, String> {
+    match ACTIONS.iter().find(|(_, name)| *name == word) {
+        Some((action, _)) => Ok(*action),
+        None => Err(format!("unknown action '{}'", word)),
+    }
+}
+
+//Tracks which actions are held, read once per frame from the keyboard
+pub struct Input {
+    bindings: Bindings,
+    path: String,
+    modified: Option<SystemTime>,
+    held: HashSet<Action>,
+    previous: HashSet<Action>,
+}
+
+impl Input {
+    pub fn load(path: &str) -> Result<Input, String> {
+        let modified = AssetMod::modifiedTime(path);
+        let bindings = Bindings::load(path)?;
+        Ok(Input{bindings, path: path.to_string(), modified, held: HashSet::new(), previous: HashSet::new()})
+    }
+
+    pub fn update(&mut self, state: &KeyboardState) {
+        let held = ACTIONS.iter()
+            .map(|(action, _)| *action)
+            .filter(|action| self.bindings.keys(*action).iter().any(|key| state.is_scancode_pressed(*key)))
+            .collect();
+        self.previous = std::mem::replace(&mut self.held, held);
+    }
+
+    //For remapping keys from inside the game, until the controls file is next reloaded
+    pub fn rebind(&mut self, action: Action, keys: &[Scancode]) {
+        self.bindings.rebind(action, keys);
+    }
+
+    pub fn isHeld(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    //True on the frame the action goes down
+    pub fn wasPressed(&self, action: Action) -> bool {
+        self.held.contains(&action) && !self.previous.contains(&action)
+    }
+
+    //Whether any action went down or up since the last frame
+    pub fn changed(&self) -> bool {
+        self.held != self.previous
+    }
+
+    //Rereads the controls file if it was saved since it was loaded, keeping the old bindings if it's broken
+    pub fn reloadChanged(&mut self) -> Result<bool, String> {
+        let modified = AssetMod::modifiedTime(&self.path);
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        self.bindings = Bindings::load(&self.path)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extraWhitespace() {
+        let bindings = Bindings::parse("bind  up   W\n    bind down S ,Down\n\tbind left A # comment\n").unwrap();
+        assert_eq!(bindings.keys(Action::MoveUp), &[Scancode::W]);
+        assert_eq!(bindings.keys(Action::MoveDown), &[Scancode::S, Scancode::Down]);
+        assert_eq!(bindings.keys(Action::MoveLeft), &[Scancode::A]);
+    }
+
+    #[test]
+    fn keyNamesWithSpaces() {
+        let bindings = Bindings::parse("bind attack Keypad   Space, Space\n").unwrap();
+        assert_eq!(bindings.keys(Action::Attack), &[Scancode::KpSpace, Scancode::Space]);
+    }
+
+    #[test]
+    fn unlistedActionsKeepTheirDefaults() {
+        let bindings = Bindings::parse("bind up W\n").unwrap();
+        assert_eq!(bindings.keys(Action::MoveDown), &[Scancode::Down]);
+        assert_eq!(bindings.keys(Action::Pause), &[Scancode::Escape, Scancode::P]);
+    }
+
+    #[test]
+    fn bindingAKeyTakesItFromOtherActions() {
+        let bindings = Bindings::parse("bind interact Space\n").unwrap();
+        assert_eq!(bindings.keys(Action::Interact), &[Scancode::Space]);
+        assert_eq!(bindings.keys(Action::Attack), &[Scancode::KpSpace]);
+    }
+
+    #[test]
+    fn malformedBindings() {
+        assert_eq!(Bindings::parse("bind up\n").unwrap_err(), "line 1: expected 'bind <action> <key>, <key>...'");
+        assert_eq!(Bindings::parse("\nbind jump Space\n").unwrap_err(), "line 2: unknown action 'jump'");
+        assert_eq!(Bindings::parse("bind up Nope\n").unwrap_err(), "line 1: unknown key 'Nope'");
+        assert_eq!(Bindings::parse("bind up W,\n").unwrap_err(), "line 1: unknown key ''");
+        assert_eq!(Bindings::parse("press up W\n").unwrap_err(), "line 1: unknown directive 'press'");
+        assert_eq!(Bindings::parse("bind up \"W\n").unwrap_err(), "line 1: unclosed quote");
+        assert_eq!(Bindings::parse("bind up W\\\n").unwrap_err(), "line 1: nothing to escape at the end of the line");
+    }
+
+    #[test]
+    fn hashOnlyStartsACommentAfterWhitespace() {
+        let bindings = Bindings::parse("# comment\nbind up W #comment\n").unwrap();
+        assert_eq!(bindings.keys(Action::MoveUp), &[Scancode::W]);
+        assert_eq!(Bindings::parse("bind up W#\n").unwrap_err(), "line 1: unknown key 'W#'");
+    }
+
+    #[test]
+    fn quotedKeys() {
+        let bindings = Bindings::parse("bind attack \",\", \"#\", \"Keypad #\" # comment\n").unwrap();
+        assert_eq!(bindings.keys(Action::Attack), &[Scancode::Comma, Scancode::NonUsHash, Scancode::KpHash]);
+    }
+
+    #[test]
+    fn escapedKeys() {
+        let bindings = Bindings::parse("bind attack \\,, \\#, Keypad \\# # comment\n").unwrap();
+        assert_eq!(bindings.keys(Action::Attack), &[Scancode::Comma, Scancode::NonUsHash, Scancode::KpHash]);
+    }
+
+    #[test]
+    fn rebindingTakesKeysFromOtherActions() {
+        let mut bindings = Bindings::default();
+        bindings.rebind(Action::Interact, &[Scancode::E, Scancode::P]);
+        assert_eq!(bindings.keys(Action::Interact), &[Scancode::E, Scancode::P]);
+        assert_eq!(bindings.keys(Action::Pause), &[Scancode::Escape]);
+    }
+
+    #[test]
+    fn controlsFile() {
+        let bindings = Bindings::load("Resources/Controls.cfg").unwrap();
+        assert_eq!(bindings.keys(Action::MoveUp), &[Scancode::Up, Scancode::W]);
+        assert_eq!(bindings.keys(Action::Attack), &[Scancode::Space, Scancode::KpSpace]);
+    }
+}
@@ -0,0 +1,411 @@
+use std::fs;
+use std::path::Path;
+
+use crate::AssetMod;
+use crate::CollisionMod::Direction;
+use crate::MapMod::CollisionType;
+use crate::TiledLoader;
+
+#[derive(Clone, Debug)]
+pub struct TileFrame {
+    pub image: String,
+    pub duration: u32, //In ticks
+}
+
+#[derive(Clone, Debug)]
+pub struct TileDef {
+    pub image: String,
+    pub frames: Vec<TileFrame>, //Animated tiles cycle through these instead of showing image
+    pub edge: Option<String>,
+    pub corner: Option<String>,
+    pub collision: CollisionType,
+}
+
+#[derive(Debug)]
+pub struct Spawn {
+    pub kind: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug)]
+pub struct Entrance {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+//Walking into the area moves the player to the named entrance of another map
+#[derive(Clone, Debug)]
+pub struct Exit {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub map: String,
+    pub entrance: String,
+}
+
+#[derive(Debug)]
+pub struct TriggerArea {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LayerKind {
+    Background,
+    Collision,
+    Foreground, //Drawn over the entities and never collides
+}
+
+#[derive(Debug)]
+pub struct LayerData {
+    pub kind: LayerKind,
+    pub tileset: Vec<TileDef>,
+    pub tiles: Vec<Vec<Option<usize>>>, //None leaves the cell empty
+}
+
+impl LayerData {
+    fn new(kind: LayerKind) -> LayerData {
+        LayerData{kind, tileset: vec![], tiles: vec![]}
+    }
+
+    pub fn width(&self) -> usize {
+        self.tiles.first().map_or(0, |row| row.len())
+    }
+
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+}
+
+#[derive(Debug)]
+pub struct MapData {
+    pub layers: Vec<LayerData>,
+    pub spawns: Vec<Spawn>,
+    pub triggers: Vec<TriggerArea>,
+    pub entrances: Vec<Entrance>,
+    pub exits: Vec<Exit>,
+    pub outside: CollisionType,
+}
+
+impl MapData {
+    pub fn findSpawn(&self, kind: &str) -> Option<&Spawn> {
+        self.spawns.iter().find(|spawn| spawn.kind == kind)
+    }
+
+    pub fn findEntrance(&self, name: &str) -> Option<&Entrance> {
+        self.entrances.iter().find(|entrance| entrance.name == name)
+    }
+}
+
+pub fn load(filename: &str) -> Result<MapData, String> {
+    if filename.ends_with(".json") || filename.ends_with(".tmj") {
+        return TiledLoader::load(filename);
+    }
+    if filename.ends_with(".tmx") {
+        return Err(format!("{}: Tiled XML maps are not supported, export the map as JSON instead", filename));
+    }
+    let source = fs::read_to_string(filename)
+        .map_err(|e| format!("{}: could not read map file: {}", filename, e))?;
+    let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, directory).map_err(|e| format!("{}: {}", filename, e))
+}
+
+//Image and map paths are resolved relative to directory, the same way Tiled maps are
+pub fn parse(source: &str, directory: &Path) -> Result<MapData, String> {
+    let mut layers: Vec<LayerData> = vec![];
+    let mut spawns = vec![];
+    let mut triggers = vec![];
+    let mut entrances = vec![];
+    let mut exits = vec![];
+    let mut outside = CollisionType::Block;
+    let mut inGrid = false;
+
+    for (lineNumber, line) in source.lines().enumerate() {
+        let lineNumber = lineNumber + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        if inGrid && !DIRECTIVES.contains(&words[0]) {
+            //A row starts with a tile, anything else is a misspelled directive
+            if words[0] != "." && words[0].parse::<usize>().is_err() {
+                return Err(format!("line {}: unknown directive '{}'", lineNumber, words[0]));
+            }
+            let layer = layers.last_mut().unwrap();
+            let mut row = vec![];
+            for word in words {
+                if word == "." {
+                    row.push(None);
+                    continue;
+                }
+                let idx = word.parse::<usize>()
+                    .map_err(|_| format!("line {}: tile index '{}' is not a number", lineNumber, word))?;
+                if idx >= layer.tileset.len() {
+                    return Err(format!("line {}: tile index {} is not in the tileset ({} entries)", lineNumber, idx, layer.tileset.len()));
+                }
+                row.push(Some(idx));
+            }
+            if layer.height() > 0 && layer.width() != row.len() {
+                return Err(format!("line {}: row has {} tiles but the first row has {}", lineNumber, row.len(), layer.width()));
+            }
+            layer.tiles.push(row);
+            continue;
+        }
+        inGrid = false;
+
+        match words[0] {
+            "layer" => {
+                let kind = match words.get(1) {
+                    Some(&"background") => LayerKind::Background,
+                    Some(&"collision") => LayerKind::Collision,
+                    Some(&"foreground") => LayerKind::Foreground,
+                    Some(other) => return Err(format!("line {}: unknown layer kind '{}'", lineNumber, other)),
+                    None => return Err(format!("line {}: expected 'layer <background|collision|foreground>'", lineNumber)),
+                };
+                layers.push(LayerData::new(kind));
+            },
+            "tile" => {
+                let image = match words.get(1) {
+                    Some(image) => AssetMod::resolve(directory, image),
+                    None => return Err(format!("line {}: expected 'tile <image> [edge=<image>] [corner=<image>] [collision]'", lineNumber)),
+                };
+                let mut edge = None;
+                let mut corner = None;
+                let mut options = 2;
+                for word in &words[2..] {
+                    if let Some(path) = word.strip_prefix("edge=") {
+                        edge = Some(AssetMod::resolve(directory, path));
+                    }
+                    else if let Some(path) = word.strip_prefix("corner=") {
+                        corner = Some(AssetMod::resolve(directory, path));
+                    }
+                    else {
+                        break;
+                    }
+                    options += 1;
+                }
+                let collision = parseCollision(&words[options..]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                //Files without layer directives put everything on a single collision layer
+                if layers.is_empty() {
+                    layers.push(LayerData::new(LayerKind::Collision));
+                }
+                let layer = layers.last_mut().unwrap();
+                if layer.height() > 0 {
+                    return Err(format!("line {}: tiles must be declared before the layer's grid", lineNumber));
+                }
+                layer.tileset.push(TileDef{image, frames: vec![], edge, corner, collision});
+            },
+            "frame" => {
+                if words.len() != 3 {
+                    return Err(format!("line {}: expected 'frame <image> <ticks>'", lineNumber));
+                }
+                let duration = parseSize(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let tile = match layers.last_mut() {
+                    Some(layer) if layer.height() == 0 => layer.tileset.last_mut(),
+                    _ => None,
+                };
+                match tile {
+                    Some(tile) => tile.frames.push(TileFrame{image: AssetMod::resolve(directory, words[1]), duration}),
+                    None => return Err(format!("line {}: frames must follow the tile they animate", lineNumber)),
+                }
+            },
+            "spawn" => {
+                if words.len() != 4 {
+                    return Err(format!("line {}: expected 'spawn <kind> <x> <y>'", lineNumber));
+                }
+                let x = parseCoordinate(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let y = parseCoordinate(words[3]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                spawns.push(Spawn{kind: words[1].to_string(), x, y});
+            },
+            "trigger" => {
+                if words.len() != 6 {
+                    return Err(format!("line {}: expected 'trigger <name> <x> <y> <width> <height>'", lineNumber));
+                }
+                let x = parseCoordinate(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let y = parseCoordinate(words[3]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let width = parseSize(words[4]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let height = parseSize(words[5]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                triggers.push(TriggerArea{name: words[1].to_string(), x, y, width, height});
+            },
+            "entrance" => {
+                if words.len() != 4 {
+                    return Err(format!("line {}: expected 'entrance <name> <x> <y>'", lineNumber));
+                }
+                let x = parseCoordinate(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let y = parseCoordinate(words[3]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                entrances.push(Entrance{name: words[1].to_string(), x, y});
+            },
+            "exit" => {
+                if words.len() != 7 {
+                    return Err(format!("line {}: expected 'exit <x> <y> <width> <height> <map> <entrance>'", lineNumber));
+                }
+                let x = parseCoordinate(words[1]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let y = parseCoordinate(words[2]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let width = parseSize(words[3]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                let height = parseSize(words[4]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+                exits.push(Exit{x, y, width, height, map: AssetMod::resolve(directory, words[5]), entrance: words[6].to_string()});
+            },
+            "outside" => {
+                if words.len() == 1 {
+                    return Err(format!("line {}: expected 'outside <collision>'", lineNumber));
+                }
+                outside = parseCollision(&words[1..]).map_err(|e| format!("line {}: {}", lineNumber, e))?;
+            },
+            "tiles" => {
+                match layers.last() {
+                    Some(layer) if !layer.tileset.is_empty() => (),
+                    _ => return Err(format!("line {}: the tileset must be declared before the tiles", lineNumber)),
+                }
+                if layers.last().unwrap().height() > 0 {
+                    return Err(format!("line {}: layer already has tiles", lineNumber));
+                }
+                inGrid = true;
+            },
+            other => return Err(format!("line {}: unknown directive '{}'", lineNumber, other)),
+        }
+    }
+
+    if layers.is_empty() {
+        return Err("map has no tiles".to_string());
+    }
+    for (i, layer) in layers.iter().enumerate() {
+        if layer.height() == 0 {
+            return Err(format!("layer {} has no tiles", i + 1));
+        }
+        if layer.width() != layers[0].width() || layer.height() != layers[0].height() {
+            return Err(format!("layer {} is {}x{} tiles but layer 1 is {}x{}",
+                i + 1, layer.width(), layer.height(), layers[0].width(), layers[0].height()));
+        }
+    }
+
+    Ok(MapData{layers, spawns, triggers, entrances, exits, outside})
+}
+
+//Any of these ends a grid, so directives can follow the tiles
+const DIRECTIVES: &[&str] = &["layer", "tile", "frame", "spawn", "trigger", "entrance", "exit", "outside", "tiles"];
+
+pub fn parseCollision(words: &[&str]) -> Result<CollisionType, String> {
+    let (collision, argumentCount) = match words {
+        [] => return Ok(CollisionType::None),
+        ["none", ..] => (CollisionType::None, 0),
+        ["block", ..] => (CollisionType::Block, 0),
+        ["hazard", ..] => (CollisionType::Hazard, 0),
+        ["slow", ..] => (CollisionType::Slow, 0),
+        ["oneway", direction, ..] => (CollisionType::OneWay(parseDirection(direction)?), 1),
+        ["oneway"] => return Err("expected 'oneway <up|down|left|right>'".to_string()),
+        ["trigger", name, ..] => (CollisionType::Trigger(name.to_string()), 1),
+        ["trigger"] => return Err("expected 'trigger <name>'".to_string()),
+        [other, ..] => return Err(format!("unknown collision type '{}'", other)),
+    };
+    if let Some(extra) = words.get(argumentCount + 1) {
+        return Err(format!("unexpected '{}' after collision type", extra));
+    }
+    Ok(collision)
+}
+
+fn parseDirection(word: &str) -> Result<Direction, String> {
+    match word {
+        "up" => Ok(Direction::Up),
+        "down" => Ok(Direction::Down),
+        "left" => Ok(Direction::Left),
+        "right" => Ok(Direction::Right),
+        other => Err(format!("unknown direction '{}'", other)),
+    }
+}
+
+fn parseCoordinate(word: &str) -> Result<i32, String> {
+    word.parse::<i32>().map_err(|_| format!("coordinate '{}' is not a number", word))
+}
+
+fn parseSize(word: &str) -> Result<u32, String> {
+    match word.parse::<u32>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format!("size '{}' is not a positive number", word)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directivesCanFollowTheGrid() {
+        let map = parse("tile a.png\ntile b.png block\ntiles\n0 1\n1 0\nspawn player 5 6\nentrance start 1 2\nexit 0 0 10 10 other.map start\n", Path::new("")).unwrap();
+        assert_eq!(map.layers[0].tiles, vec![vec![Some(0), Some(1)], vec![Some(1), Some(0)]]);
+        assert_eq!((map.spawns[0].x, map.spawns[0].y), (5, 6));
+        assert_eq!(map.entrances[0].name, "start");
+        assert_eq!(map.exits[0].map, "other.map");
+    }
+
+    #[test]
+    fn pathsAreRelativeToTheMap() {
+        let map = parse("tile ../Images/a.png edge=./b.png\nframe ../Images/c.png 5\ntiles\n0\nexit 0 0 10 10 Other.map start\n", Path::new("Resources/Maps")).unwrap();
+        assert_eq!(map.layers[0].tileset[0].image, "Resources/Images/a.png");
+        assert_eq!(map.layers[0].tileset[0].edge.as_deref(), Some("Resources/Maps/b.png"));
+        assert_eq!(map.layers[0].tileset[0].frames[0].image, "Resources/Images/c.png");
+        assert_eq!(map.exits[0].map, "Resources/Maps/Other.map");
+    }
+
+    #[test]
+    fn levels() {
+        let level1 = load("Resources/Maps/Level1.map").unwrap();
+        assert_eq!(level1.layers[0].tileset[0].image, "Resources/Images/Ground.png");
+        assert_eq!(level1.exits[0].map, "Resources/Maps/Level2.map");
+        let level2 = load(&level1.exits[0].map).unwrap();
+        assert_eq!(level2.exits[0].map, "Resources/Maps/Level1.map");
+    }
+
+    #[test]
+    fn raggedRows() {
+        assert_eq!(parse("tile a.png\ntiles\n0 0 0\n0 0\n", Path::new("")).unwrap_err(), "line 4: row has 2 tiles but the first row has 3");
+    }
+
+    #[test]
+    fn indexOutOfRange() {
+        assert_eq!(parse("tile a.png\ntile b.png\ntiles\n0 2\n", Path::new("")).unwrap_err(), "line 4: tile index 2 is not in the tileset (2 entries)");
+    }
+
+    #[test]
+    fn indexNotANumber() {
+        assert_eq!(parse("tile a.png\ntiles\n0 x\n", Path::new("")).unwrap_err(), "line 3: tile index 'x' is not a number");
+    }
+
+    #[test]
+    fn missingTileset() {
+        assert_eq!(parse("tiles\n0 0\n", Path::new("")).unwrap_err(), "line 1: the tileset must be declared before the tiles");
+        assert_eq!(parse("layer background\ntiles\n", Path::new("")).unwrap_err(), "line 2: the tileset must be declared before the tiles");
+    }
+
+    #[test]
+    fn tileAfterTheGrid() {
+        assert_eq!(parse("tile a.png\ntiles\n0\ntile b.png\n", Path::new("")).unwrap_err(), "line 4: tiles must be declared before the layer's grid");
+    }
+
+    #[test]
+    fn emptyMap() {
+        assert_eq!(parse("# nothing here\n", Path::new("")).unwrap_err(), "map has no tiles");
+        assert_eq!(parse("tile a.png\n", Path::new("")).unwrap_err(), "layer 1 has no tiles");
+    }
+
+    #[test]
+    fn mismatchedLayers() {
+        assert_eq!(parse("layer background\ntile a.png\ntiles\n0 0\nlayer collision\ntile b.png\ntiles\n0\n", Path::new("")).unwrap_err(), "layer 2 is 1x1 tiles but layer 1 is 2x1");
+    }
+
+    #[test]
+    fn unknownDirective() {
+        assert_eq!(parse("tile a.png\ntiles\n0\nspwan player 0 0\n", Path::new("")).unwrap_err(), "line 4: unknown directive 'spwan'");
+        assert_eq!(parse("tyle a.png\n", Path::new("")).unwrap_err(), "line 1: unknown directive 'tyle'");
+    }
+}
@@ -1,102 +1,661 @@
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, TextureCreator};
-use sdl2::video::{Window, WindowContext};
+use sdl2::rect::{Point, Rect};
 
-use crate::SpriteLoader::Sprites;
-use crate::CollisionMod::Collision;
+use crate::AssetMod::Assets;
+use crate::RenderMod::{RenderLayer, RenderQueue};
+use crate::SpriteLoader::{DrawParams, Sprites, StandardAnimation};
+use crate::CameraMod::Camera;
+use crate::CollisionMod::{self, Collision, Direction, HitTarget, Overlap, RayHit};
+use crate::MapLoader::{Exit, LayerData, LayerKind, MapData};
+
+pub const TILE_SIZE: u32 = 50;
 
-#[derive(Default)]
 struct Tile {
-    idx: usize,
+    sprite: usize,
+    animation: Option<usize>,
+    angle: f64,
+    flipHorizontal: bool,
+    flipVertical: bool,
 }
 
 impl Tile {
-    fn new(idx: usize) -> Tile {
-        Tile {idx}
+    fn new(sprite: usize) -> Tile {
+        Tile {sprite, animation: None, angle: 0f64, flipHorizontal: false, flipVertical: false}
+    }
+
+    fn animated(sprite: usize, animation: usize) -> Tile {
+        Tile {sprite, animation: Some(animation), angle: 0f64, flipHorizontal: false, flipVertical: false}
+    }
+
+    fn rotated(sprite: usize, angle: f64) -> Tile {
+        Tile {sprite, animation: None, angle, flipHorizontal: false, flipVertical: false}
     }
+
+    fn flipped(sprite: usize, flipHorizontal: bool, flipVertical: bool) -> Tile {
+        Tile {sprite, animation: None, angle: 0f64, flipHorizontal, flipVertical}
+    }
+}
+
+//Sprites used to blend a tile into differing neighbours.
+//The edge image has its border along the top and the corner image along the top and left.
+#[derive(Default)]
+struct AutoTile {
+    edge: Option<usize>,
+    corner: Option<usize>,
 }
 
 struct TileRenderer<'a> {
     textures: Sprites<'a>,
+    animations: Vec<StandardAnimation<'a>>,
 }
 
 impl<'a> TileRenderer<'a> {
-    fn new(textures: Sprites) -> TileRenderer {
-        TileRenderer{textures}
+    fn new(textures: Sprites<'a>, animations: Vec<StandardAnimation<'a>>) -> TileRenderer<'a> {
+        TileRenderer{textures, animations}
     }
-    fn render(&self, tile: &Tile, quad: Rect, canvas: &mut Canvas<Window>) {
-        self.textures.getSprite(tile.idx).draw(canvas, quad, false, false);
+    fn render(&self, tile: &Tile, quad: Rect, queue: &mut RenderQueue<'a>, layer: RenderLayer, clock: u32) {
+        let sprite = match tile.animation {
+            Some(animation) => self.animations[animation].getFrameAt(clock),
+            None => self.textures.getSprite(tile.sprite),
+        };
+        queue.push(layer, 0, sprite, quad, &DrawParams{angle: tile.angle, flipHorizontal: tile.flipHorizontal, flipVertical: tile.flipVertical, ..DrawParams::default()});
     }
 }
 
-enum CollisionType {
+#[derive(Clone, PartialEq, Debug)]
+pub enum CollisionType {
     Block,
+    Hazard,
+    Slow,
+    OneWay(Direction), //Can only be crossed while moving in this direction
+    Trigger(String),
     None,
 }
 
+impl CollisionType {
+    pub fn blocks(&self, direction: Direction) -> bool {
+        match self {
+            CollisionType::Block => true,
+            CollisionType::OneWay(allowed) => *allowed == direction.opposite(),
+            _ => false,
+        }
+    }
+}
+
 impl Default for CollisionType {
     fn default() -> Self {
         CollisionType::None
     }
 }
-pub struct Map<'a> {
-    tiles: [[Tile; 17]; 12],
-    collisionMap: [[CollisionType; 17]; 12],
+struct Layer<'a> {
+    kind: LayerKind,
+    tiles: Vec<Option<Tile>>,
     renderer: TileRenderer<'a>,
 }
 
-impl<'a> Map <'a> {
-    
-    pub fn new(map: [[usize; 17]; 12], creator: &TextureCreator<WindowContext>) -> Map {
-        let mut tiles: [[Tile; 17]; 12] = Default::default();
-        let mut collisionMap: [[CollisionType; 17]; 12] = Default::default();
-        
-        for column in map.iter().enumerate() {
-            
-            for tile in column.1.iter().enumerate() {
-                
-                tiles[column.0][tile.0] = Tile::new(*tile.1);
-
-                if *tile.1 == 1 {
-                    collisionMap[column.0][tile.0] = CollisionType::Block;
+impl<'a> Layer<'a> {
+    fn new(layer: &LayerData, assets: &Assets<'a>) -> Result<Layer<'a>, String> {
+        let mut filenames: Vec<&str> = layer.tileset.iter().map(|tile| tile.image.as_str()).collect();
+        let mut autoTiles = vec![];
+        for tile in layer.tileset.iter() {
+            let mut autoTile = AutoTile::default();
+            if let Some(edge) = &tile.edge {
+                autoTile.edge = Some(filenames.len());
+                filenames.push(edge);
+            }
+            if let Some(corner) = &tile.corner {
+                autoTile.corner = Some(filenames.len());
+                filenames.push(corner);
+            }
+            autoTiles.push(autoTile);
+        }
+
+        //Animated tileset entries get their own frame sequence, indexed in tileset order
+        let mut animations = vec![];
+        let mut animationIds = vec![];
+        for tile in layer.tileset.iter() {
+            if tile.frames.is_empty() {
+                animationIds.push(None);
+                continue;
+            }
+            let frameFiles: Vec<&str> = tile.frames.iter().map(|frame| frame.image.as_str()).collect();
+            let positions: Vec<usize> = (0..frameFiles.len()).collect();
+            let durations: Vec<u32> = tile.frames.iter().map(|frame| frame.duration).collect();
+            animations.push(StandardAnimation::fromFilesTimed(assets, &frameFiles, &positions, &durations)?);
+            animationIds.push(Some(animations.len() - 1));
+        }
+
+        let mut tiles = Vec::with_capacity(layer.width() * layer.height());
+        for (y, row) in layer.tiles.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                tiles.push(tile.map(|tile| match animationIds[tile] {
+                    Some(animation) => Tile::animated(tile, animation),
+                    None => autoTile(layer, x, y, &autoTiles[tile]),
+                }));
+            }
+        }
+
+        let sprites = Sprites::new(assets, &filenames)?;
+        Ok(Layer{kind: layer.kind, tiles, renderer: TileRenderer::new(sprites, animations)})
+    }
+}
+
+//The collision of every tile, built from the map's layers without loading any images
+pub struct CollisionGrid {
+    width: usize,
+    height: usize,
+    collisionMap: Vec<CollisionType>,
+    outOfBounds: CollisionType,
+}
+
+impl CollisionGrid {
+    pub fn new(map: &MapData) -> Result<CollisionGrid, String> {
+        let height = map.layers.first().map_or(0, |layer| layer.height());
+        let width = map.layers.first().map_or(0, |layer| layer.width());
+        if width == 0 {
+            return Err("Map has no tiles".to_string());
+        }
+
+        let mut collisionMap = vec![CollisionType::None; width * height];
+        for layer in map.layers.iter() {
+            if layer.width() != width || layer.height() != height || layer.tiles.iter().any(|row| row.len() != width) {
+                return Err(format!("Map layers must all be {}x{} tiles", width, height));
+            }
+            //Later layers override the collisions of earlier ones, except where they are empty
+            if layer.kind != LayerKind::Foreground {
+                for (y, row) in layer.tiles.iter().enumerate() {
+                    for (x, tile) in row.iter().enumerate() {
+                        if let Some(tile) = tile {
+                            collisionMap[y * width + x] = layer.tileset[*tile].collision.clone();
+                        }
+                    }
                 }
+            }
+        }
 
+        Ok(CollisionGrid{width, height, collisionMap, outOfBounds: map.outside.clone()})
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn collisionAt(&self, x: i32, y: i32) -> &CollisionType {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return &self.outOfBounds;
+        }
+        &self.collisionMap[y as usize * self.width + x as usize]
+    }
+
+    pub fn collisionsIn(&self, hitbox: Rect) -> Vec<&CollisionType> {
+        let tileSize = TILE_SIZE as i32;
+        let leftBound = hitbox.left().div_euclid(tileSize);
+        let rightBound = (hitbox.right() - 1).div_euclid(tileSize);
+        let topBound = hitbox.top().div_euclid(tileSize);
+        let bottomBound = (hitbox.bottom() - 1).div_euclid(tileSize);
+        let mut collisions = vec![];
+        for y in topBound..=bottomBound {
+            for x in leftBound..=rightBound {
+                collisions.push(self.collisionAt(x, y));
             }
+        }
+        collisions
+    }
+}
+
+pub struct Map<'a> {
+    grid: CollisionGrid,
+    layers: Vec<Layer<'a>>,
+    triggers: Vec<(String, Rect)>,
+    exits: Vec<Exit>,
+    clock: u32,
+}
+
+impl<'a> Map <'a> {
+    
+    pub fn new(map: &MapData, assets: &Assets<'a>) -> Result<Map<'a>, String> {
+        let grid = CollisionGrid::new(map)?;
 
+        let mut layers = vec![];
+        for layer in map.layers.iter() {
+            layers.push(Layer::new(layer, assets)?);
         }
 
-        let sprites = Sprites::new(creator, &[&"Resources/Images/Ground.png", &"Resources/Images/Wall.png"]).unwrap();
-        let renderer = TileRenderer::new(sprites);
-        Map {tiles, collisionMap, renderer}
+        let triggers = map.triggers.iter()
+            .map(|trigger| (trigger.name.clone(), Rect::new(trigger.x, trigger.y, trigger.width, trigger.height)))
+            .collect();
+
+        Ok(Map {grid, layers, triggers, exits: map.exits.clone(), clock: 0})
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid.height()
     }
 
-    pub fn render(&self, canvas: &mut Canvas<Window>) {
-        let mut quad = Rect::new(0, 0, 50, 50);
-        for column in self.tiles.iter() {
-            for tile in column {
-                self.renderer.render(&tile, quad, canvas);
-                quad.reposition((quad.x + 50, quad.y));
+    pub fn pixelWidth(&self) -> u32 {
+        self.width() as u32 * TILE_SIZE
+    }
+
+    pub fn pixelHeight(&self) -> u32 {
+        self.height() as u32 * TILE_SIZE
+    }
+
+    pub fn grid(&self) -> &CollisionGrid {
+        &self.grid
+    }
+
+    pub fn collisionsIn(&self, hitbox: Rect) -> Vec<&CollisionType> {
+        self.grid.collisionsIn(hitbox)
+    }
+
+    //Names of the trigger tiles and trigger areas the hitbox touches, without repeats
+    pub fn triggersIn(&self, hitbox: Rect) -> Vec<String> {
+        let mut triggers: Vec<String> = vec![];
+        for collision in self.collisionsIn(hitbox) {
+            if let CollisionType::Trigger(name) = collision {
+                if !triggers.contains(name) {
+                    triggers.push(name.clone());
+                }
+            }
+        }
+        for (name, area) in self.triggers.iter() {
+            if area.has_intersection(hitbox) && !triggers.contains(name) {
+                triggers.push(name.clone());
             }
-            quad.reposition((0, quad.y + 50));
         }
+        triggers
+    }
+
+    pub fn exitAt(&self, hitbox: Rect) -> Option<&Exit> {
+        self.exits.iter().find(|exit| Rect::new(exit.x, exit.y, exit.width, exit.height).has_intersection(hitbox))
+    }
+
+    //Advances animated tiles by one tick
+    pub fn update(&mut self) {
+        self.clock = self.clock.wrapping_add(1);
     }
 
+    //Queues every layer's tiles, foreground layers go over the entities and the rest beneath them
+    pub fn render(&self, queue: &mut RenderQueue<'a>, camera: &Camera) {
+        let view = camera.view();
+        let tileSize = TILE_SIZE as i32;
+
+        //Only the tiles overlapping the viewport are drawn
+        let left = (view.left().max(0) / tileSize) as usize;
+        let top = (view.top().max(0) / tileSize) as usize;
+        let right = ((view.right().max(0) + tileSize - 1) / tileSize) as usize;
+        let bottom = ((view.bottom().max(0) + tileSize - 1) / tileSize) as usize;
+
+        for layer in self.layers.iter() {
+            let renderLayer = if layer.kind == LayerKind::Foreground {RenderLayer::Foreground} else {RenderLayer::Ground};
+            for y in top..bottom.min(self.height()) {
+                for x in left..right.min(self.width()) {
+                    if let Some(tile) = &layer.tiles[y * self.width() + x] {
+                        let quad = Rect::new(x as i32 * tileSize, y as i32 * tileSize, TILE_SIZE, TILE_SIZE);
+                        layer.renderer.render(tile, camera.toScreen(quad), queue, renderLayer, self.clock);
+                    }
+                }
+            }
+        }
+    }
+
+}
+
+//Picks the edge or corner variant of a tile based on which neighbours use a different image
+fn autoTile(layer: &LayerData, x: usize, y: usize, autoTile: &AutoTile) -> Tile {
+    let idx = layer.tiles[y][x].unwrap();
+    let differs = |dx: i32, dy: i32| {
+        let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+        if nx < 0 || ny < 0 {
+            return false;
+        }
+        match layer.tiles.get(ny as usize).and_then(|row| row.get(nx as usize)) {
+            Some(Some(neighbour)) => layer.tileset[*neighbour].image != layer.tileset[idx].image,
+            _ => false,
+        }
+    };
+
+    let (up, down, left, right) = (differs(0, -1), differs(0, 1), differs(-1, 0), differs(1, 0));
+
+    match (autoTile.edge, autoTile.corner, up, down, left, right) {
+        (_, Some(corner), true, false, true, false) => Tile::flipped(corner, false, false),
+        (_, Some(corner), true, false, false, true) => Tile::flipped(corner, true, false),
+        (_, Some(corner), false, true, true, false) => Tile::flipped(corner, false, true),
+        (_, Some(corner), false, true, false, true) => Tile::flipped(corner, true, true),
+        (Some(edge), _, true, false, false, false) => Tile::rotated(edge, 0f64),
+        (Some(edge), _, false, false, false, true) => Tile::rotated(edge, 90f64),
+        (Some(edge), _, false, true, false, false) => Tile::rotated(edge, 180f64),
+        (Some(edge), _, false, false, true, false) => Tile::rotated(edge, 270f64),
+        _ => Tile::new(idx),
+    }
 }
 
 impl<'a> Collision for Map<'a> {
     fn doesCollide(&self, hitbox: Rect) -> bool {
-        let leftBound = (hitbox.x as f32 / 50f32 ).floor() as usize;
-        let rightBound = ((hitbox.x + hitbox.w) as f32 / 50f32 ).ceil() as usize;
-        let topBound = (hitbox.y as f32 / 50f32 ).floor() as usize;
-        let bottomBound = ((hitbox.y + hitbox.h) as f32 / 50f32 ).ceil() as usize;
-        for y in topBound..bottomBound {
-            for x in leftBound..rightBound {
-                match self.collisionMap[y][x] {
-                    CollisionType::Block => return true,
-                    _ => (),
+        self.grid.doesCollide(hitbox)
+    }
+
+    fn overlap(&self, hitbox: Rect) -> Option<Overlap> {
+        self.grid.overlap(hitbox)
+    }
+
+    fn containsPoint(&self, point: Point) -> bool {
+        self.grid.containsPoint(point)
+    }
+
+    fn raycast(&self, start: Point, end: Point) -> Option<RayHit> {
+        self.grid.raycast(start, end)
+    }
+
+    fn sweep(&self, hitbox: Rect, direction: Direction, distance: u32) -> u32 {
+        self.grid.sweep(hitbox, direction, distance)
+    }
+}
+
+impl Collision for CollisionGrid {
+    fn doesCollide(&self, hitbox: Rect) -> bool {
+        self.collisionsIn(hitbox).into_iter().any(|collision| *collision == CollisionType::Block)
+    }
+
+    fn overlap(&self, hitbox: Rect) -> Option<Overlap> {
+        let tileSize = TILE_SIZE as i32;
+        let mut targets = vec![];
+        let mut covered: Option<Rect> = None;
+        for y in hitbox.top().div_euclid(tileSize)..=(hitbox.bottom() - 1).div_euclid(tileSize) {
+            for x in hitbox.left().div_euclid(tileSize)..=(hitbox.right() - 1).div_euclid(tileSize) {
+                if *self.collisionAt(x, y) == CollisionType::Block {
+                    let tile = Rect::new(x * tileSize, y * tileSize, TILE_SIZE, TILE_SIZE);
+                    covered = Some(covered.map_or(tile, |covered| covered.union(tile)));
+                    targets.push(HitTarget::Tile(x, y));
+                }
+            }
+        }
+        covered.map(|covered| Overlap{targets, penetration: CollisionMod::penetration(hitbox, covered)})
+    }
+
+    fn containsPoint(&self, point: Point) -> bool {
+        let tileSize = TILE_SIZE as i32;
+        *self.collisionAt(point.x().div_euclid(tileSize), point.y().div_euclid(tileSize)) == CollisionType::Block
+    }
+
+    //Walks the tiles the ray passes through in order instead of testing every pixel
+    fn raycast(&self, start: Point, end: Point) -> Option<RayHit> {
+        let tileSize = TILE_SIZE as f32;
+        let (x0, y0) = (start.x() as f32, start.y() as f32);
+        let (dx, dy) = (end.x() as f32 - x0, end.y() as f32 - y0);
+        let mut tileX = start.x().div_euclid(TILE_SIZE as i32);
+        let mut tileY = start.y().div_euclid(TILE_SIZE as i32);
+        let endX = end.x().div_euclid(TILE_SIZE as i32);
+        let endY = end.y().div_euclid(TILE_SIZE as i32);
+
+        let stepX = dx.signum() as i32;
+        let stepY = dy.signum() as i32;
+        let deltaX = if dx == 0f32 {f32::INFINITY} else {tileSize / dx.abs()};
+        let deltaY = if dy == 0f32 {f32::INFINITY} else {tileSize / dy.abs()};
+        let mut nextX = if dx > 0f32 {((tileX + 1) as f32 * tileSize - x0) / dx}
+            else if dx < 0f32 {(x0 - tileX as f32 * tileSize) / -dx}
+            else {f32::INFINITY};
+        let mut nextY = if dy > 0f32 {((tileY + 1) as f32 * tileSize - y0) / dy}
+            else if dy < 0f32 {(y0 - tileY as f32 * tileSize) / -dy}
+            else {f32::INFINITY};
+
+        let mut t = 0f32;
+        loop {
+            if *self.collisionAt(tileX, tileY) == CollisionType::Block {
+                let point = Point::new((x0 + dx * t).round() as i32, (y0 + dy * t).round() as i32);
+                return Some(RayHit{target: HitTarget::Tile(tileX, tileY), point, distance: CollisionMod::distance(start, point)});
+            }
+            if tileX == endX && tileY == endY {
+                return None;
+            }
+            if nextX < nextY {
+                t = nextX;
+                nextX += deltaX;
+                tileX += stepX;
+            }
+            else {
+                t = nextY;
+                nextY += deltaY;
+                tileY += stepY;
+            }
+            if t > 1f32 {
+                return None;
+            }
+        }
+    }
+
+    fn sweep(&self, hitbox: Rect, direction: Direction, distance: u32) -> u32 {
+        let tileSize = TILE_SIZE as i32;
+        let distance = distance as i32;
+        let horizontal = direction == Direction::Left || direction == Direction::Right;
+
+        //Rows or columns the leading edge moves into, in the order they are reached
+        let lines: Vec<i32> = match direction {
+            Direction::Right => {
+                let edge = hitbox.right() - 1;
+                (edge.div_euclid(tileSize) + 1..=(edge + distance).div_euclid(tileSize)).collect()
+            },
+            Direction::Down => {
+                let edge = hitbox.bottom() - 1;
+                (edge.div_euclid(tileSize) + 1..=(edge + distance).div_euclid(tileSize)).collect()
+            },
+            Direction::Left => {
+                let edge = hitbox.left();
+                ((edge - distance).div_euclid(tileSize)..edge.div_euclid(tileSize)).rev().collect()
+            },
+            Direction::Up => {
+                let edge = hitbox.top();
+                ((edge - distance).div_euclid(tileSize)..edge.div_euclid(tileSize)).rev().collect()
+            },
+        };
+
+        let (crossStart, crossEnd) = if horizontal {
+            (hitbox.top(), hitbox.bottom() - 1)
+        }
+        else {
+            (hitbox.left(), hitbox.right() - 1)
+        };
+
+        for line in lines {
+            for cross in crossStart.div_euclid(tileSize)..=crossEnd.div_euclid(tileSize) {
+                let (x, y) = if horizontal {(line, cross)} else {(cross, line)};
+                if self.collisionAt(x, y).blocks(direction) {
+                    let allowed = match direction {
+                        Direction::Right => line * tileSize - hitbox.right(),
+                        Direction::Down => line * tileSize - hitbox.bottom(),
+                        Direction::Left => hitbox.left() - (line + 1) * tileSize,
+                        Direction::Up => hitbox.top() - (line + 1) * tileSize,
+                    };
+                    return allowed as u32;
                 }
             }
         }
-        false
+        distance as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MapLoader;
+
+    //A 3x3 map with a wall in the middle
+    fn grid(outside: &str) -> CollisionGrid {
+        let source = format!("outside {}\ntile a.png\ntile b.png block\ntile c.png hazard\ntiles\n0 0 0\n0 1 0\n0 0 2\n", outside);
+        CollisionGrid::new(&MapLoader::parse(&source, std::path::Path::new("")).unwrap()).unwrap()
+    }
+
+    fn blocks(collisions: &[&CollisionType]) -> usize {
+        collisions.iter().filter(|collision| ***collision == CollisionType::Block).count()
+    }
+
+    #[test]
+    fn negativeCoordinatesAreOutside() {
+        let grid = grid("block");
+        assert!(*grid.collisionAt(-1, 0) == CollisionType::Block);
+        assert!(*grid.collisionAt(0, -1) == CollisionType::Block);
+        assert!(*grid.collisionAt(-1, -1) == CollisionType::Block);
+        assert!(*grid.collisionAt(i32::MIN, i32::MIN) == CollisionType::Block);
+        assert!(*grid.collisionAt(0, 0) == CollisionType::None);
+    }
+
+    #[test]
+    fn lastColumnAndRow() {
+        let grid = grid("block");
+        assert!(*grid.collisionAt(2, 0) == CollisionType::None);
+        assert!(*grid.collisionAt(0, 2) == CollisionType::None);
+        assert!(*grid.collisionAt(2, 2) == CollisionType::Hazard);
+        assert!(*grid.collisionAt(3, 0) == CollisionType::Block);
+        assert!(*grid.collisionAt(0, 3) == CollisionType::Block);
+        assert!(*grid.collisionAt(3, 3) == CollisionType::Block);
+    }
+
+    #[test]
+    fn outsideNone() {
+        let grid = grid("none");
+        assert!(*grid.collisionAt(-1, -1) == CollisionType::None);
+        assert!(*grid.collisionAt(3, 1) == CollisionType::None);
+        assert!(!grid.doesCollide(Rect::new(-100, -100, 50, 50)));
+        assert!(grid.doesCollide(Rect::new(60, 60, 10, 10)));
+    }
+
+    #[test]
+    fn hitboxesStraddlingCorners() {
+        let size = 3 * TILE_SIZE as i32;
+        //Each box covers one tile of the map and three tiles outside it
+        let corners = [(-10, -10), (size - 10, -10), (-10, size - 10), (size - 10, size - 10)];
+        for (x, y) in corners.iter() {
+            let hitbox = Rect::new(*x, *y, 20, 20);
+            let closed = grid("block");
+            assert_eq!(closed.collisionsIn(hitbox).len(), 4);
+            assert_eq!(blocks(&closed.collisionsIn(hitbox)), 3);
+            assert!(closed.doesCollide(hitbox));
+            let open = grid("none");
+            assert_eq!(blocks(&open.collisionsIn(hitbox)), 0);
+            assert!(!open.doesCollide(hitbox));
+        }
+    }
+
+    #[test]
+    fn hitboxesOnEdges() {
+        let grid = grid("block");
+        let size = 3 * TILE_SIZE as i32;
+        //Flush against each edge from the inside
+        assert!(!grid.doesCollide(Rect::new(0, 0, 50, 50)));
+        assert!(!grid.doesCollide(Rect::new(size - 50, 0, 50, 50)));
+        assert!(!grid.doesCollide(Rect::new(0, size - 100, 50, 50)));
+        //One pixel over each edge
+        assert!(grid.doesCollide(Rect::new(-1, 0, 50, 50)));
+        assert!(grid.doesCollide(Rect::new(0, -1, 50, 50)));
+        assert!(grid.doesCollide(Rect::new(size - 49, 0, 50, 50)));
+        assert!(grid.doesCollide(Rect::new(0, size - 49, 50, 50)));
+    }
+
+    #[test]
+    fn zeroDistanceSweep() {
+        let grid = grid("block");
+        let hitbox = Rect::new(0, 0, 50, 50);
+        for direction in [Direction::Up, Direction::Down, Direction::Left, Direction::Right].iter() {
+            assert_eq!(grid.sweep(hitbox, *direction, 0), 0);
+        }
+    }
+
+    #[test]
+    fn sweepStopsAtTheEdges() {
+        let grid = grid("block");
+        let hitbox = Rect::new(10, 10, 20, 20);
+        assert_eq!(grid.sweep(hitbox, Direction::Left, 1000), 10);
+        assert_eq!(grid.sweep(hitbox, Direction::Up, 1000), 10);
+        assert_eq!(grid.sweep(hitbox, Direction::Right, 1000), 120);
+        assert_eq!(grid.sweep(hitbox, Direction::Down, 1000), 120);
+        assert_eq!(grid.sweep(Rect::new(0, 0, 50, 50), Direction::Left, 5), 0);
+    }
+
+    #[test]
+    fn sweepLeavesTheMapWhenOutsideIsOpen() {
+        let grid = grid("none");
+        let hitbox = Rect::new(10, 10, 20, 20);
+        assert_eq!(grid.sweep(hitbox, Direction::Left, 1000), 1000);
+        assert_eq!(grid.sweep(hitbox, Direction::Up, 1000), 1000);
+        assert_eq!(grid.sweep(hitbox, Direction::Right, 1000), 1000);
+        assert_eq!(grid.sweep(hitbox, Direction::Down, 1000), 1000);
+    }
+
+    #[test]
+    fn sweepStopsAtWalls() {
+        let grid = grid("none");
+        //Moving into the wall in the middle from each side
+        assert_eq!(grid.sweep(Rect::new(0, 60, 20, 20), Direction::Right, 1000), 30);
+        assert_eq!(grid.sweep(Rect::new(130, 60, 20, 20), Direction::Left, 1000), 30);
+        assert_eq!(grid.sweep(Rect::new(60, 0, 20, 20), Direction::Down, 1000), 30);
+        assert_eq!(grid.sweep(Rect::new(60, 130, 20, 20), Direction::Up, 1000), 30);
+        //Short of the wall
+        assert_eq!(grid.sweep(Rect::new(0, 60, 20, 20), Direction::Right, 29), 29);
+    }
+
+    #[test]
+    fn containsPoint() {
+        let grid = grid("block");
+        assert!(grid.containsPoint(Point::new(75, 75)));
+        assert!(grid.containsPoint(Point::new(50, 50)));
+        assert!(!grid.containsPoint(Point::new(49, 75)));
+        assert!(!grid.containsPoint(Point::new(100, 75)));
+        assert!(grid.containsPoint(Point::new(-1, 0)));
+        assert!(!self::grid("none").containsPoint(Point::new(-1, 0)));
+    }
+
+    #[test]
+    fn raysAlongGridLines() {
+        let grid = grid("none");
+        //A ray on a tile boundary belongs to the row or column below or right of it
+        let hit = grid.raycast(Point::new(0, 50), Point::new(149, 50)).unwrap();
+        assert!(hit.target == HitTarget::Tile(1, 1));
+        assert_eq!(hit.point, Point::new(50, 50));
+        assert_eq!(hit.distance, 50f32);
+        assert!(grid.raycast(Point::new(0, 49), Point::new(149, 49)).is_none());
+        assert!(grid.raycast(Point::new(100, 0), Point::new(100, 149)).is_none());
+        let hit = grid.raycast(Point::new(99, 0), Point::new(99, 149)).unwrap();
+        assert_eq!(hit.point, Point::new(99, 50));
+    }
+
+    #[test]
+    fn raysThroughCorners() {
+        let grid = grid("none");
+        let hit = grid.raycast(Point::new(0, 0), Point::new(149, 149)).unwrap();
+        assert!(hit.target == HitTarget::Tile(1, 1));
+        assert_eq!(hit.point, Point::new(50, 50));
+        let hit = grid.raycast(Point::new(149, 149), Point::new(0, 0)).unwrap();
+        assert!(hit.target == HitTarget::Tile(1, 1));
+        assert_eq!(hit.point, Point::new(100, 100));
+        //Past the wall's corner without touching it
+        assert!(grid.raycast(Point::new(110, 0), Point::new(149, 39)).is_none());
+    }
+
+    #[test]
+    fn raysStartingOutOfBounds() {
+        let hit = grid("block").raycast(Point::new(-10, 75), Point::new(40, 75)).unwrap();
+        assert!(hit.target == HitTarget::Tile(-1, 1));
+        assert_eq!(hit.point, Point::new(-10, 75));
+        assert_eq!(hit.distance, 0f32);
+        let hit = grid("none").raycast(Point::new(-100, 75), Point::new(149, 75)).unwrap();
+        assert!(hit.target == HitTarget::Tile(1, 1));
+        assert_eq!(hit.point, Point::new(50, 75));
+        assert_eq!(hit.distance, 150f32);
+    }
+
+    #[test]
+    fn raysOfNoLength() {
+        let grid = grid("none");
+        assert!(grid.raycast(Point::new(25, 25), Point::new(25, 25)).is_none());
+        let hit = grid.raycast(Point::new(75, 75), Point::new(75, 75)).unwrap();
+        assert!(hit.target == HitTarget::Tile(1, 1));
+        assert_eq!(hit.distance, 0f32);
     }
 }
@@ -0,0 +1,229 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use sdl2::rect::Point;
+
+use crate::CollisionMod::Direction;
+use crate::MapMod::{CollisionGrid, CollisionType, TILE_SIZE};
+
+const DIRECTIONS: [Direction; 4] = [Direction::Up, Direction::Down, Direction::Left, Direction::Right];
+
+//Cost of stepping onto a tile, None if it can't be entered in that direction
+fn stepCost(collision: &CollisionType, direction: Direction) -> Option<u32> {
+    if collision.blocks(direction) {
+        return None;
+    }
+    match collision {
+        CollisionType::Slow => Some(3),
+        CollisionType::Hazard => Some(10),
+        _ => Some(1),
+    }
+}
+
+struct Grid<'a> {
+    map: &'a CollisionGrid,
+    footprint: (i32, i32), //Tiles covered by the entity
+}
+
+impl<'a> Grid<'a> {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0
+            && x + self.footprint.0 <= self.map.width() as i32
+            && y + self.footprint.1 <= self.map.height() as i32
+    }
+
+    //The entity's top left tile is at (x, y), every tile it covers has to allow the step
+    fn cost(&self, x: i32, y: i32, direction: Direction) -> Option<u32> {
+        if !self.contains(x, y) {
+            return None;
+        }
+        let mut total = 0;
+        for dy in 0..self.footprint.1 {
+            for dx in 0..self.footprint.0 {
+                total = total.max(stepCost(self.map.collisionAt(x + dx, y + dy), direction)?);
+            }
+        }
+        Some(total)
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        y as usize * self.map.width() + x as usize
+    }
+
+    //The tile nearest to the entity's top left corner
+    fn tileOf(&self, position: Point) -> (i32, i32) {
+        let tileSize = TILE_SIZE as i32;
+        (
+            (position.x() + tileSize / 2).div_euclid(tileSize),
+            (position.y() + tileSize / 2).div_euclid(tileSize),
+        )
+    }
+
+    //Where the entity's top left corner goes to sit centred on the footprint at (x, y)
+    fn positionOf(&self, x: i32, y: i32, size: (u32, u32)) -> Point {
+        let tileSize = TILE_SIZE as i32;
+        Point::new(
+            x * tileSize + (self.footprint.0 * tileSize - size.0 as i32) / 2,
+            y * tileSize + (self.footprint.1 * tileSize - size.1 as i32) / 2,
+        )
+    }
+}
+
+//Finds a path for an entity of the given pixel size from start to goal, both positions of its top left corner.
+//Returns the corners of the path, starting at the nearest tile and ending at the goal tile, or None if the goal can't be reached.
+pub fn findPath(map: &CollisionGrid, start: Point, goal: Point, size: (u32, u32)) -> Option<Vec<Point>> {
+    let footprint = (
+        size.0.div_ceil(TILE_SIZE).max(1) as i32,
+        size.1.div_ceil(TILE_SIZE).max(1) as i32,
+    );
+    let grid = Grid{map, footprint};
+    let start = grid.tileOf(start);
+    let goal = grid.tileOf(goal);
+    if !grid.contains(start.0, start.1) || !grid.contains(goal.0, goal.1) {
+        return None;
+    }
+
+    let heuristic = |x: i32, y: i32| ((x - goal.0).abs() + (y - goal.1).abs()) as u32;
+
+    let mut costs = vec![u32::MAX; map.width() * map.height()];
+    let mut cameFrom: Vec<Option<(i32, i32)>> = vec![None; map.width() * map.height()];
+    let mut open = BinaryHeap::new();
+
+    costs[grid.index(start.0, start.1)] = 0;
+    open.push(Reverse((heuristic(start.0, start.1), start.0, start.1)));
+
+    while let Some(Reverse((_, x, y))) = open.pop() {
+        if (x, y) == goal {
+            return Some(buildPath(&grid, &cameFrom, goal, size));
+        }
+        let cost = costs[grid.index(x, y)];
+        for direction in DIRECTIONS.iter() {
+            let (dx, dy) = direction.unit();
+            let (nx, ny) = (x + dx, y + dy);
+            let step = match grid.cost(nx, ny, *direction) {
+                Some(step) => step,
+                None => continue,
+            };
+            let next = grid.index(nx, ny);
+            if cost + step < costs[next] {
+                costs[next] = cost + step;
+                cameFrom[next] = Some((x, y));
+                open.push(Reverse((cost + step + heuristic(nx, ny), nx, ny)));
+            }
+        }
+    }
+    None
+}
+
+fn buildPath(grid: &Grid, cameFrom: &[Option<(i32, i32)>], goal: (i32, i32), size: (u32, u32)) -> Vec<Point> {
+    let mut tiles = vec![goal];
+    while let Some(previous) = cameFrom[grid.index(tiles.last().unwrap().0, tiles.last().unwrap().1)] {
+        tiles.push(previous);
+    }
+    tiles.reverse();
+
+    //Line up with the grid first, then only keep the tiles where the path turns, plus the goal
+    let mut waypoints = vec![grid.positionOf(tiles[0].0, tiles[0].1, size)];
+    for i in 1..tiles.len() {
+        let last = i == tiles.len() - 1;
+        let turns = !last && (tiles[i].0 - tiles[i - 1].0, tiles[i].1 - tiles[i - 1].1) != (tiles[i + 1].0 - tiles[i].0, tiles[i + 1].1 - tiles[i].1);
+        if last || turns {
+            waypoints.push(grid.positionOf(tiles[i].0, tiles[i].1, size));
+        }
+    }
+    waypoints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::MapLoader;
+
+    //0 floor, 1 wall, 2 slow, 3 hazard, 4 one way going right
+    fn grid(rows: &str) -> CollisionGrid {
+        let source = format!("outside block\ntile a.png\ntile b.png block\ntile c.png slow\ntile d.png hazard\ntile e.png oneway right\ntiles\n{}", rows);
+        CollisionGrid::new(&MapLoader::parse(&source, Path::new("")).unwrap()).unwrap()
+    }
+
+    fn tile(x: i32, y: i32) -> Point {
+        Point::new(x * TILE_SIZE as i32, y * TILE_SIZE as i32)
+    }
+
+    const SMALL: (u32, u32) = (TILE_SIZE, TILE_SIZE);
+    const TALL: (u32, u32) = (TILE_SIZE, 2 * TILE_SIZE);
+
+    //Every tile the path's straight runs go over
+    fn tilesOn(path: &[Point]) -> Vec<(i32, i32)> {
+        let tileSize = TILE_SIZE as i32;
+        let mut tiles = vec![(path[0].x() / tileSize, path[0].y() / tileSize)];
+        for pair in path.windows(2) {
+            let (mut x, mut y) = (pair[0].x() / tileSize, pair[0].y() / tileSize);
+            let (endX, endY) = (pair[1].x() / tileSize, pair[1].y() / tileSize);
+            assert!(x == endX || y == endY, "paths only move along rows and columns");
+            while (x, y) != (endX, endY) {
+                x += (endX - x).signum();
+                y += (endY - y).signum();
+                tiles.push((x, y));
+            }
+        }
+        tiles
+    }
+
+    #[test]
+    fn aroundAWall() {
+        let grid = grid("0 0 0 0 0\n0 0 1 0 0\n0 0 1 0 0\n");
+        let path = findPath(&grid, tile(0, 2), tile(4, 2), SMALL).unwrap();
+        assert_eq!(path.first(), Some(&tile(0, 2)));
+        assert_eq!(path.last(), Some(&tile(4, 2)));
+        let tiles = tilesOn(&path);
+        assert!(tiles.iter().all(|(x, y)| *grid.collisionAt(*x, *y) != CollisionType::Block));
+        assert!(tiles.contains(&(2, 0)));
+        assert_eq!(tiles.len(), 9);
+    }
+
+    #[test]
+    fn unreachableGoal() {
+        let grid = grid("0 1 0\n0 1 0\n");
+        assert!(findPath(&grid, tile(0, 0), tile(2, 0), SMALL).is_none());
+    }
+
+    #[test]
+    fn tallEntitiesNeedTallGaps() {
+        let narrow = grid("0 1 0\n0 0 0\n0 1 0\n0 1 0\n");
+        assert!(findPath(&narrow, tile(0, 0), tile(2, 0), TALL).is_none());
+        assert!(findPath(&narrow, tile(0, 0), tile(2, 0), SMALL).is_some());
+        let wide = grid("0 1 0\n0 0 0\n0 0 0\n0 1 0\n");
+        let path = findPath(&wide, tile(0, 0), tile(2, 0), TALL).unwrap();
+        assert!(tilesOn(&path).contains(&(1, 1)));
+    }
+
+    #[test]
+    fn slowAndHazardTilesAreAvoided() {
+        for slow in &["2", "3"] {
+            let rows = format!("0 0 0 0 0\n0 {0} {0} {0} 0\n", slow);
+            let path = findPath(&grid(&rows), tile(0, 1), tile(4, 1), SMALL).unwrap();
+            let tiles = tilesOn(&path);
+            assert!(!tiles.contains(&(2, 1)));
+            assert!(tiles.contains(&(2, 0)));
+            //Without a detour they are still crossed
+            let rows = format!("0 {} 0\n", slow);
+            assert!(findPath(&grid(&rows), tile(0, 0), tile(2, 0), SMALL).is_some());
+        }
+    }
+
+    #[test]
+    fn oneWayTiles() {
+        let grid = grid("0 4 0\n");
+        assert!(findPath(&grid, tile(0, 0), tile(2, 0), SMALL).is_some());
+        assert!(findPath(&grid, tile(2, 0), tile(0, 0), SMALL).is_none());
+    }
+
+    #[test]
+    fn startOrGoalOutside() {
+        let grid = grid("0 0\n0 0\n");
+        assert!(findPath(&grid, tile(-1, 0), tile(1, 1), SMALL).is_none());
+        assert!(findPath(&grid, tile(0, 0), tile(2, 1), SMALL).is_none());
+        assert!(findPath(&grid, tile(0, 0), tile(0, 1), TALL).is_none());
+    }
+}
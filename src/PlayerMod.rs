@@ -1,202 +1,231 @@
 extern crate sdl2;
 
-use sdl2::EventPump;
 use sdl2::mixer::Channel;
-use sdl2::render::Canvas;
 //use sdl2::render::Texture;
-use sdl2::render::TextureCreator;
-use sdl2::video::{WindowContext, Window};
-use sdl2::rect::Rect;
-use sdl2::keyboard::{KeyboardState, Scancode};
-
-use crate::CollisionMod::Collision;
-use crate::MapMod::Map;
-use crate::SpriteLoader::FlipAnimation;
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+
+use crate::AssetMod::Assets;
+use crate::CameraMod::Camera;
+use crate::CollisionMod::{self, Collision, Direction};
+use crate::InputMod::{Action, Input};
+use crate::MapMod::{CollisionType, Map};
+use crate::RenderMod::{RenderLayer, RenderQueue, Renderer};
 use crate::SpriteLoader::Sprites;
-use crate::Skeleton;
+use crate::SkeletonMod::Skeleton;
 
-use super::SpriteLoader::{Animation, StandardAnimation, Animations};
+use super::SpriteLoader::{Animations, DrawParams};
 
 struct Vector(i32, i32);
 
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
 pub struct Player<'a> {
     animations: Animations<'a>,
     sword: Sprites<'a>,
-    timer: u32,
     position: Rect,
     hitbox: Rect,
     velocity: Vector,
     direction: Direction,
-    attackTimer: u32,
-    trapped: bool,
+    swordHitting: bool,
+    spawn: (i32, i32),
+    health: u32,
+    invulnerableTimer: u32,
+    activeTriggers: Vec<String>,
 }
 
 impl<'a> Player<'a> {
-    pub fn new(creator: &'a TextureCreator<WindowContext>, x: i32, y: i32) -> Player<'a> {
-        let mut animations = vec![];
-        for animation in SPRITES {
-            let idxs: Vec<usize> = 
-                (0..animation.len())
-                .chain((1..animation.len() - 1)
-                    .rev()
-                )
-                .collect();
-            
-            let animation = Animation::Standard(StandardAnimation::fromFiles(creator, *animation, &idxs).unwrap());
-            animations.push(animation);
-        }
-        let left = Animation::Flip(FlipAnimation::new(1));
-        let leftAttack = Animation::Flip(FlipAnimation::new(4));
-        animations.push(left);
-        animations.push(leftAttack);
-        let animations = Animations::new(animations);
+    pub fn new(assets: &Assets<'a>, x: i32, y: i32) -> Result<Player<'a>, String> {
+        let mut animations = Animations::fromFile(assets, ANIMATIONS)?;
+        animations.setParameter("direction", directionName(Direction::Down)).map_err(|e| format!("{}: {}", ANIMATIONS, e))?;
         let position = Rect::new(x, y, 50, 50);
         let hitbox = Rect::new(x + 2, y + 2, 46, 46);
         let velocity = Vector(0, 0);
-        let sword = Sprites::new(creator, &[&"Resources/Images/Sword.png"]).unwrap();
-        Player{animations, sword, timer: 0, position, hitbox, velocity, direction: Direction::Down, attackTimer: 0, trapped: false,}
+        let sword = Sprites::new(assets, &[&"Resources/Images/Sword.png"])?;
+        Ok(Player{animations, sword, position, hitbox, velocity, direction: Direction::Down, swordHitting: false,
+            spawn: (x, y), health: MAX_HEALTH, invulnerableTimer: 0, activeTriggers: vec![],})
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) {
-        if self.attackTimer > 0 {
-            match self.direction {
-                Direction::Down => {
-                    self.animations.drawNextFrame(canvas, self.position);
-                    self.sword.getSprite(0).draw(canvas,
-                    Rect::new(SWORD_DOWN.0 + self.position.x(),
-                    SWORD_DOWN.1 + self.position.y(),
-                    SWORD_DOWN.2,
-                    SWORD_DOWN.3), false, true);
-                },
-                Direction::Left => {
-                    self.sword.getSprite(0).draw(canvas,
-                    Rect::new(SWORD_LEFT.0 + self.position.x(),
-                    SWORD_LEFT.1 + self.position.y(),
-                    SWORD_LEFT.2,
-                    SWORD_LEFT.3), false, false);
-                    self.animations.drawNextFrame(canvas, self.position);
-                },
-                Direction::Right => {
-                    self.sword.getSprite(0).draw(canvas,
-                    Rect::new(SWORD_RIGHT.0 + self.position.x(),
-                    SWORD_RIGHT.1 + self.position.y(),
-                    SWORD_RIGHT.2,
-                    SWORD_RIGHT.3), false, false);
-                    self.animations.drawNextFrame(canvas, self.position);
-                },
-                Direction::Up => {
-                    self.sword.getSprite(0).draw(canvas,
-                    Rect::new(SWORD_UP.0 + self.position.x(),
-                    SWORD_UP.1 + self.position.y(),
-                    SWORD_UP.2,
-                    SWORD_UP.3), false, false);
-                    self.animations.drawNextFrame(canvas, self.position);
-                },
-            }
+    pub fn position(&self) -> Rect {
+        self.position
+    }
+
+    pub fn hitbox(&self) -> Rect {
+        self.hitbox
+    }
+
+    //Moves the player into another room, which also becomes where they respawn
+    pub fn teleport(&mut self, x: i32, y: i32) {
+        self.spawn = (x, y);
+        self.position.reposition((x, y));
+        self.hitbox.reposition((x + 2, y + 2));
+        self.activeTriggers.clear();
+    }
+
+    pub fn draw(&self, queue: &mut RenderQueue<'a>, camera: &Camera) {
+        let position = camera.toScreen(self.position);
+        let mut params = DrawParams::default();
+        //Blink red while invulnerable after being hurt
+        if self.invulnerableTimer / FLASH_TIME % 2 == 1 {
+            params.tint = DAMAGE_TINT;
         }
-        else {
-            self.animations.drawNextFrame(canvas, self.position);
-    
+        let key = self.hitbox.bottom();
+        self.animations.queueNextFrame(queue, key, position, &params);
+        if self.isAttacking() {
+            self.drawSword(queue, camera, key, self.swordPose());
+        }
+    }
+
+    //One box per point of health in the top left corner, drawn over the world
+    pub fn drawHealth(&self, renderer: &mut dyn Renderer) {
+        for point in 0..MAX_HEALTH {
+            let x = HEALTH_MARGIN + point as i32 * (HEALTH_SIZE as i32 + HEALTH_MARGIN);
+            renderer.fillRect(Rect::new(x, HEALTH_MARGIN, HEALTH_SIZE, HEALTH_SIZE), if point < self.health {HEALTH_COLOR} else {LOST_HEALTH_COLOR});
         }
     }
 
-    pub fn update(&mut self, state: Option<KeyboardState>, events: &EventPump, mut channel: Channel, map: &Map, skeleton: &mut Skeleton) -> Channel {
-        if let Some(state) = state {
-            self.checkKeyboardInput(&state);
+    //The sword swings through SWORD_SWING degrees about its hilt over the attack
+    fn drawSword(&self, queue: &mut RenderQueue<'a>, camera: &Camera, key: i32, pose: &SwordPose) {
+        let quad = camera.toScreen(self.relTupleToRect(pose.rect));
+        let hilt = if pose.flipVertical {Point::new(quad.width() as i32 / 2, 0)} else {Point::new(quad.width() as i32 / 2, quad.height() as i32)};
+        let angle = SWORD_SWING * (self.animations.progress() - 0.5);
+        let params = DrawParams{angle, pivot: Some(hilt), flipVertical: pose.flipVertical, ..DrawParams::default()};
+        queue.push(RenderLayer::Entities, key + pose.depth, self.sword.getSprite(0), quad, &params);
+    }
+
+    fn swordPose(&self) -> &'static SwordPose {
+        match self.direction {
+            Direction::Down => &SWORD_DOWN,
+            Direction::Left => &SWORD_LEFT,
+            Direction::Right => &SWORD_RIGHT,
+            Direction::Up => &SWORD_UP,
         }
+    }
 
-        self.position.reposition((self.position.x + self.velocity.0, self.position.y));
-        self.hitbox.reposition((self.hitbox.x + self.velocity.0, self.hitbox.y));
+    pub fn update(&mut self, input: &Input, mut channel: Channel, map: &Map, skeletons: &mut [Skeleton]) -> Result<Channel, String> {
+        if input.changed() {
+            self.checkInput(input)?;
+        }
 
-        if map.doesCollide(self.hitbox) || skeleton.doesCollide(self.hitbox) {
-            self.position.reposition((self.position.x - self.velocity.0, self.position.y));
-            self.hitbox.reposition((self.hitbox.x - self.velocity.0, self.hitbox.y));
+        let slowed = map.collisionsIn(self.hitbox).contains(&&CollisionType::Slow);
+        let velocity = if slowed {
+            Vector(self.velocity.0 / 2, self.velocity.1 / 2)
         }
+        else {
+            Vector(self.velocity.0, self.velocity.1)
+        };
 
-        self.position.reposition((self.position.x, self.position.y + self.velocity.1));
-        self.hitbox.reposition((self.hitbox.x, self.hitbox.y + self.velocity.1));
+        let mut colliders: Vec<&dyn Collision> = vec![map];
+        colliders.extend(skeletons.iter().map(|skeleton| skeleton as &dyn Collision));
+        let movement = CollisionMod::moveAndSlide(&colliders, self.hitbox, velocity.0, velocity.1);
+        self.moveBy(movement.dx, movement.dy);
 
-        if map.doesCollide(self.hitbox) || skeleton.doesCollide(self.hitbox) {
-            self.position.reposition((self.position.x, self.position.y - self.velocity.1));
-            self.hitbox.reposition((self.hitbox.x, self.hitbox.y - self.velocity.1));
+        //Something may have appeared on top of the player, like a gate closing
+        for skeleton in skeletons.iter() {
+            if let Some(overlap) = skeleton.overlap(self.hitbox) {
+                self.moveBy(overlap.penetration.0, overlap.penetration.1);
+            }
         }
 
-        if !self.trapped {
-            if self.position.x() >= THRESHOLD {
-                channel = skeleton.trapPlayer(channel);
-                self.trapped = true;
+        let collisions = map.collisionsIn(self.hitbox);
+
+        if self.invulnerableTimer > 0 {
+            self.invulnerableTimer -= 1;
+        }
+        else if collisions.contains(&&CollisionType::Hazard) {
+            self.damage();
+        }
+
+        let triggers = map.triggersIn(self.hitbox);
+        for name in triggers.iter() {
+            if !self.activeTriggers.contains(name) {
+                channel = self.onTrigger(name, channel, skeletons);
             }
         }
+        self.activeTriggers = triggers;
         
-        self.timer += 1;
+        let wasAttacking = self.isAttacking();
+        self.animations.update()?;
+        //Attacking only asks for an attack on the tick the button goes down
+        self.animations.setParameter("attacking", "false")?;
+        for event in self.animations.events() {
+            match event.as_str() {
+                "hit_start" => self.swordHitting = true,
+                "hit_end" => self.swordHitting = false,
+                _ => (),
+            }
+        }
+
+        //The attack lasts exactly as long as its animation
+        if wasAttacking && !self.isAttacking() {
+            self.swordHitting = false;
+            self.checkInput(input)?;
+        }
+        Ok(channel)
+    }
+
+    fn moveBy(&mut self, dx: i32, dy: i32) {
+        self.position.offset(dx, dy);
+        self.hitbox.offset(dx, dy);
+    }
 
-        if self.timer > 20 {
-            self.timer = 0;
-            self.animations.update();
+    fn damage(&mut self) {
+        self.health -= 1;
+        self.invulnerableTimer = INVULNERABILITY_TIME;
+        if self.health == 0 {
+            self.health = MAX_HEALTH;
+            self.position.reposition(self.spawn);
+            self.hitbox.reposition((self.spawn.0 + 2, self.spawn.1 + 2));
         }
+    }
 
-        if self.attackTimer > 0 {
-            self.attackTimer -= 1;
-            if self.attackTimer == 0 {
-                match &self.direction {
-                    Direction::Up => self.animations.changeAnimation(2),
-                    Direction::Down => self.animations.changeAnimation(0),
-                    Direction::Left => self.animations.changeAnimation(6),
-                    Direction::Right => self.animations.changeAnimation(1),
-                }.unwrap();
-                self.checkKeyboardInput(&events.keyboard_state());
+    fn onTrigger(&mut self, name: &str, mut channel: Channel, skeletons: &mut [Skeleton]) -> Channel {
+        if name == "trap" {
+            for skeleton in skeletons.iter_mut() {
+                channel = skeleton.trapPlayer(channel);
             }
         }
         channel
     }
 
-    pub fn checkKeyboardInput(&mut self, state: &KeyboardState) {
-        if self.attackTimer > 0 {return ();}
+    pub fn checkInput(&mut self, input: &Input) -> Result<(), String> {
+        if self.isAttacking() {return Ok(());}
 
-        if state.is_scancode_pressed(Scancode::Down) {
+        if input.isHeld(Action::MoveDown) {
             self.velocity.1 = 3;
-            self.animations.changeAnimation(0).unwrap();
-            self.direction = Direction::Down;
+            self.face(Direction::Down)?;
         }
-        else if state.is_scancode_pressed(Scancode::Up) {
+        else if input.isHeld(Action::MoveUp) {
             self.velocity.1 = -3;
-            self.animations.changeAnimation(2).unwrap();
-            self.direction = Direction::Up;
+            self.face(Direction::Up)?;
         }
         else {
             self.velocity.1 = 0;
         }
 
-        if state.is_scancode_pressed(Scancode::Left) {
+        if input.isHeld(Action::MoveLeft) {
             self.velocity.0 = -3;
-            self.animations.changeAnimation(6).unwrap();
-            self.direction = Direction::Left;
+            self.face(Direction::Left)?;
         }
-        else if state.is_scancode_pressed(Scancode::Right) {
+        else if input.isHeld(Action::MoveRight) {
             self.velocity.0 = 3;
-            self.animations.changeAnimation(1).unwrap();
-            self.direction = Direction::Right; 
+            self.face(Direction::Right)?;
         }
         else {
             self.velocity.0 = 0;
         }
-        if state.is_scancode_pressed(Scancode::Space) || state.is_scancode_pressed(Scancode::KpSpace) {
+        if input.isHeld(Action::Attack) {
             self.velocity = Vector(0, 0);
-            self.attackTimer = 21;
-            match &self.direction {
-                Direction::Up => self.animations.changeAnimation(5),
-                Direction::Down => self.animations.changeAnimation(3),
-                Direction::Left => self.animations.changeAnimation(7),
-                Direction::Right => self.animations.changeAnimation(4),
-            }.unwrap();    
+            self.animations.setParameter("attacking", "true")?;
         }
+        Ok(())
+    }
+
+    fn face(&mut self, direction: Direction) -> Result<(), String> {
+        self.direction = direction;
+        self.animations.setParameter("direction", directionName(direction))
+    }
+
+    fn isAttacking(&self) -> bool {
+        self.animations.state() == "attack"
     }
 
     fn relTupleToRect(&self, coords: (i32, i32, u32, u32)) -> Rect {
@@ -209,7 +238,7 @@ impl<'a> Player<'a> {
     }
 
     pub fn attackCollision(&self, hitbox: Rect) -> bool {
-        if self.attackTimer == 0 {
+        if !self.swordHitting {
             return false;
         }
         let coords = match self.direction {
@@ -226,54 +255,41 @@ impl<'a> Player<'a> {
 
 
 
-pub const THRESHOLD: i32 = 450;
+fn directionName(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+const ANIMATIONS: &str = "Resources/Animations/Ninja.anim";
+
+const MAX_HEALTH: u32 = 3;
+const INVULNERABILITY_TIME: u32 = 60;
+const FLASH_TIME: u32 = 6; //Ticks between the blinks while invulnerable
+const DAMAGE_TINT: Color = Color{r: 0xff, g: 0x40, b: 0x40, a: 0xff};
+const HEALTH_SIZE: u32 = 16;
+const HEALTH_MARGIN: i32 = 8;
+const HEALTH_COLOR: Color = Color{r: 0xd0, g: 0x10, b: 0x10, a: 0xff};
+const LOST_HEALTH_COLOR: Color = Color{r: 0x40, g: 0x10, b: 0x10, a: 0xff};
+
+const SWORD_SWING: f64 = 90f64;
+
+//Where the sword is drawn relative to the player, and whether it goes in front of them (positive depth) or behind
+struct SwordPose {
+    rect: (i32, i32, u32, u32),
+    depth: i32,
+    flipVertical: bool,
+}
 
-const SWORD_DOWN: (i32, i32, u32, u32) = (10, 43, 30, 30);
-const SWORD_RIGHT: (i32, i32, u32, u32) = (30, 5, 30, 30);
-const SWORD_LEFT: (i32, i32, u32, u32) = (-10, 5, 30, 30);
-const SWORD_UP: (i32, i32, u32, u32) = (0, -10, 50, 50);
+const SWORD_DOWN: SwordPose = SwordPose{rect: (10, 43, 30, 30), depth: 1, flipVertical: true};
+const SWORD_RIGHT: SwordPose = SwordPose{rect: (30, 5, 30, 30), depth: -1, flipVertical: false};
+const SWORD_LEFT: SwordPose = SwordPose{rect: (-10, 5, 30, 30), depth: -1, flipVertical: false};
+const SWORD_UP: SwordPose = SwordPose{rect: (0, -10, 50, 50), depth: -1, flipVertical: false};
 
 const SWORD_DOWN_COLLISION: (i32, i32, u32, u32) = (23, 43, 4, 16);
 const SWORD_RIGHT_COLLISION: (i32, i32, u32, u32) = (43, 5, 4, 16);
 const SWORD_LEFT_COLLISION: (i32, i32, u32, u32) = (3, 5, 4, 16);
 const SWORD_UP_COLLISION: (i32, i32, u32, u32) = (27, -10, 6, 27);
-
-
-const NINJA_FLOAT: &[&str] = &[    
-    &"Resources/Images/Ninja_float_0__half.png",
-    &"Resources/Images/Ninja_float_1__half.png",
-    &"Resources/Images/Ninja_float_2__half.png",
-];
-
-const NINJA_RIGHT_FLOAT: &[&str] = &[
-    &"Resources/Images/Ninja_right_float_0.png",
-    &"Resources/Images/Ninja_right_float_1.png",
-    &"Resources/Images/Ninja_right_float_2.png",
-];
-
-const NINJA_UP_FLOAT: &[&str] = &[
-    &"Resources/Images/Ninja_up_float_0__half.png",
-    &"Resources/Images/Ninja_up_float_1__half.png",
-    &"Resources/Images/Ninja_up_float_2__half.png",
-];
-
-const NINJA_ATTACK: &[&str] = &[
-    &"Resources/Images/Ninja_attack__half.png",
-];
-
-const NINJA_UP_ATTACK: &[&str] = &[
-    &"Resources/Images/Ninja_up_attack__half.png",
-];
-
-const NINJA_RIGHT_ATTACK: &[&str] = &[
-    &"Resources/Images/Ninja_right_attack.png",
-];
-
-const SPRITES: &[&[&str]] = &[
-    NINJA_FLOAT,
-    NINJA_RIGHT_FLOAT,
-    NINJA_UP_FLOAT,
-    NINJA_ATTACK,
-    NINJA_RIGHT_ATTACK,
-    NINJA_UP_ATTACK,
-];
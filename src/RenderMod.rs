@@ -0,0 +1,120 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, RenderTarget};
+
+use crate::SpriteLoader::{DrawParams, Sprite};
+
+//Everything the game draws goes through a Renderer, so it can run without a window
+pub trait Renderer {
+    fn clear(&mut self, color: Color);
+    fn drawSprite(&mut self, sprite: &Sprite, quad: Rect, params: &DrawParams);
+    fn fillRect(&mut self, rect: Rect, color: Color);
+    fn present(&mut self);
+}
+
+impl<T: RenderTarget> Renderer for Canvas<T> {
+    fn clear(&mut self, color: Color) {
+        self.set_draw_color(color);
+        Canvas::clear(self);
+    }
+
+    fn drawSprite(&mut self, sprite: &Sprite, quad: Rect, params: &DrawParams) {
+        sprite.copyTo(self, quad, params);
+    }
+
+    fn fillRect(&mut self, rect: Rect, color: Color) {
+        self.set_draw_color(color);
+        self.fill_rect(rect).unwrap();
+    }
+
+    fn present(&mut self) {
+        Canvas::present(self);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderLayer {
+    Ground, //Map layers beneath the entities
+    Entities,
+    Foreground, //Map layers drawn over the entities
+}
+
+struct QueuedSprite<'a> {
+    layer: RenderLayer,
+    key: i32,
+    sprite: Sprite<'a>,
+    quad: Rect,
+    params: DrawParams,
+}
+
+//Collects a frame's sprites and draws them by layer, then by key. Entities use the y of their
+//feet as the key so whatever is lower on the screen is drawn in front. Equal keys keep the order they were queued in.
+pub struct RenderQueue<'a> {
+    sprites: Vec<QueuedSprite<'a>>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> RenderQueue<'a> {
+        RenderQueue{sprites: vec![]}
+    }
+
+    pub fn push(&mut self, layer: RenderLayer, key: i32, sprite: &Sprite<'a>, quad: Rect, params: &DrawParams) {
+        self.sprites.push(QueuedSprite{layer, key, sprite: sprite.clone(), quad, params: *params});
+    }
+
+    //Draws everything queued and empties the queue for the next frame
+    pub fn flush(&mut self, renderer: &mut dyn Renderer) {
+        self.sprites.sort_by_key(|queued| (queued.layer, queued.key));
+        for queued in self.sprites.drain(..) {
+            queued.sprite.drawWith(renderer, queued.quad, &queued.params);
+        }
+    }
+}
+
+pub enum DrawCommand {
+    Clear(Color),
+    Sprite{image: String, source: Option<Rect>, quad: Rect, params: DrawParams},
+    FillRect(Rect, Color),
+}
+
+//Keeps a list of what would have been drawn instead of drawing it
+pub struct RecordingRenderer {
+    commands: Vec<DrawCommand>,
+    lastFrame: Vec<DrawCommand>,
+    frames: u32,
+}
+
+impl RecordingRenderer {
+    pub fn new() -> RecordingRenderer {
+        RecordingRenderer{commands: vec![], lastFrame: vec![], frames: 0}
+    }
+
+    //How many frames have been presented
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    //What was drawn in the last presented frame, in order
+    pub fn lastFrame(&self) -> &[DrawCommand] {
+        &self.lastFrame
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn clear(&mut self, color: Color) {
+        self.commands.push(DrawCommand::Clear(color));
+    }
+
+    fn drawSprite(&mut self, sprite: &Sprite, quad: Rect, params: &DrawParams) {
+        self.commands.push(DrawCommand::Sprite{image: sprite.image().to_string(), source: sprite.source(), quad, params: *params});
+    }
+
+    fn fillRect(&mut self, rect: Rect, color: Color) {
+        self.commands.push(DrawCommand::FillRect(rect, color));
+    }
+
+    fn present(&mut self) {
+        self.lastFrame = std::mem::take(&mut self.commands);
+        self.frames += 1;
+    }
+}
@@ -1,15 +1,26 @@
+use std::rc::Rc;
+
 use sdl2::mixer::Channel;
 use sdl2::mixer::Chunk;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::render::TextureCreator;
-use sdl2::video::{WindowContext, Window};
+use sdl2::rect::{Point, Rect};
 
-use crate::PlayerMod;
+use crate::AssetMod::Assets;
+use crate::CameraMod::Camera;
+use crate::CollisionMod::{self, Collision, HitTarget, Overlap};
+use crate::MapMod::Map;
+use crate::PathfindingMod;
 use crate::PlayerMod::Player;
+use crate::RenderMod::{RenderLayer, RenderQueue};
 use crate::SpriteLoader::Animations;
 use crate::SpriteLoader::Animation;
-use crate::SpriteLoader::Sprites;
+use crate::SpriteLoader::{DrawParams, Sprites};
+
+//What a room remembers about its skeleton after the player leaves
+#[derive(Clone, Copy, Default)]
+pub struct SkeletonState {
+    pub playerIsTrapped: bool,
+    pub defeated: bool,
+}
 
 pub struct Skeleton<'a> {
     sprites: Sprites<'a>,
@@ -17,63 +28,91 @@ pub struct Skeleton<'a> {
     hitbox: Rect,
     timer: usize,
     playerIsTrapped: bool,
+    chasing: bool, //Set once the trapped player has been seen
+    defeated: bool,
+    fadeTimer: u32, //Counts down while a defeated skeleton fades away
+    path: Vec<Point>,
+    pathTimer: u32,
     gateHitBox: Rect,
-    gateSound: Chunk,
-    skeletonDie: Chunk
+    gateSound: Rc<Chunk>,
+    skeletonDie: Rc<Chunk>
 }
 
 impl<'a> Skeleton<'a> {
-    pub fn new(creator: &TextureCreator<WindowContext>, x: i32, y: i32) -> Skeleton {
-        let sprites = Sprites::new(creator, SKELETON_SPRITES).unwrap();
+    pub fn new(assets: &Assets<'a>, x: i32, y: i32, gate: (i32, i32)) -> Result<Skeleton<'a>, String> {
+        let sprites = Sprites::new(assets, SKELETON_SPRITES)?;
         let position = Rect::new(x, y, 50, 50);
         let hitbox = Rect::new(x, y, 50, 100);
-        let gateHitBox = Rect::new(PlayerMod::THRESHOLD - 50 , 150, 50, 50);
-        let gateSound = Chunk::from_file(&"Resources/Music/Gate Sound.wav").unwrap();
-        let skeletonDie = Chunk::from_file(&"Resources/Music/Skeleton Die.wav").unwrap();
-        Skeleton{sprites, hitbox, position, timer: 0, playerIsTrapped: false, gateHitBox, gateSound, skeletonDie}
+        let gateHitBox = Rect::new(gate.0, gate.1, 50, 50);
+        let gateSound = assets.chunk("Resources/Music/Gate Sound.wav")?;
+        let skeletonDie = assets.chunk("Resources/Music/Skeleton Die.wav")?;
+        Ok(Skeleton{sprites, hitbox, position, timer: 0, playerIsTrapped: false, chasing: false, defeated: false, fadeTimer: 0,
+            path: vec![], pathTimer: 0, gateHitBox, gateSound, skeletonDie})
+    }
+
+    pub fn state(&self) -> SkeletonState {
+        SkeletonState{playerIsTrapped: self.playerIsTrapped, defeated: self.defeated}
     }
 
-    pub fn draw(&self, canvas: &mut Canvas<Window>) {
+    pub fn restore(&mut self, state: SkeletonState) {
+        self.playerIsTrapped = state.playerIsTrapped;
+        self.defeated = state.defeated;
+    }
+
+    pub fn draw(&self, queue: &mut RenderQueue<'a>, camera: &Camera) {
+        if self.defeated && self.fadeTimer == 0 {
+            return;
+        }
+        let mut params = DrawParams::default();
+        if self.defeated {
+            params.alpha = (255 * self.fadeTimer / FADE_TIME) as u8;
+        }
+        let position = camera.toScreen(self.position);
+        let legs = Rect::new(
+            position.x(),
+            position.y() + 50,
+            position.width(),
+            position.height(),
+        );
         if self.playerIsTrapped {
             let state = (self.timer as f32 / 15f32 + 1f32).floor() as usize;
-            self.sprites.getSprite(0).draw(canvas, self.position, false, false);
-            self.sprites.getSprite(state).draw(canvas, Rect::new(
-                self.position.x(),
-                self.position.y() + 50,
-                self.position.width(),
-                self.position.height(),
-            ), false, false);
-            self.sprites.getSprite(3).draw(canvas, self.gateHitBox, false, false)
+            queue.push(RenderLayer::Entities, self.hitbox.bottom(), self.sprites.getSprite(0), position, &params);
+            queue.push(RenderLayer::Entities, self.hitbox.bottom(), self.sprites.getSprite(state), legs, &params);
+            queue.push(RenderLayer::Entities, self.gateHitBox.bottom(), self.sprites.getSprite(3), camera.toScreen(self.gateHitBox), &params)
         }
         else {
-            self.sprites.getSprite(0).draw(canvas, self.position, false, false);
-            self.sprites.getSprite(1).draw(canvas, Rect::new(
-                self.position.x(),
-                self.position.y() + 50,
-                self.position.width(),
-                self.position.height(),
-            ), false, false);
+            queue.push(RenderLayer::Entities, self.hitbox.bottom(), self.sprites.getSprite(0), position, &params);
+            queue.push(RenderLayer::Entities, self.hitbox.bottom(), self.sprites.getSprite(1), legs, &params);
         }
     }
 
     pub fn trapPlayer(&mut self, channel: Channel) -> Channel {
+        if self.defeated || self.playerIsTrapped {
+            return channel;
+        }
         self.playerIsTrapped = true;
         channel.play(&self.gateSound, 0).unwrap()
     }
 
-    pub fn doesCollide(&self, hitbox: Rect) -> bool {
-        if self.playerIsTrapped {
-            return self.gateHitBox.has_intersection(hitbox)
-        }
-        false
-    }
-
-    pub fn update(&mut self, player: &Player, mut channel: Channel) -> Channel {
+    pub fn update(&mut self, player: &Player, map: &Map, mut channel: Channel) -> Channel {
         
         if self.playerIsTrapped && player.attackCollision(self.hitbox) {
             self.playerIsTrapped = false;
+            self.defeated = true;
+            self.fadeTimer = FADE_TIME;
             channel = channel.play(&self.skeletonDie, 0).unwrap();
         }
+
+        if self.fadeTimer > 0 {
+            self.fadeTimer -= 1;
+        }
+
+        if self.playerIsTrapped && !self.chasing {
+            self.chasing = self.canSee(player, map);
+        }
+        if self.playerIsTrapped && self.chasing {
+            self.chase(player, map);
+        }
         
         self.timer += 1;
 
@@ -83,17 +122,65 @@ impl<'a> Skeleton<'a> {
 
         channel
     }
+
+    //Nothing but open floor between the skeleton's middle and the player's
+    fn canSee(&self, player: &Player, map: &Map) -> bool {
+        CollisionMod::hasLineOfSight(&[map], self.hitbox.center(), player.position().center())
+    }
+
+    fn chase(&mut self, player: &Player, map: &Map) {
+        if self.pathTimer == 0 {
+            let size = (self.hitbox.width(), self.hitbox.height());
+            self.path = PathfindingMod::findPath(map.grid(), self.hitbox.top_left(), player.position().top_left(), size)
+                .unwrap_or_default();
+            self.pathTimer = REPATH_TIME;
+        }
+        self.pathTimer -= 1;
+
+        if let Some(target) = self.path.first() {
+            let dx = (target.x() - self.hitbox.x()).signum() * SPEED;
+            let dy = (target.y() - self.hitbox.y()).signum() * SPEED;
+            self.position.offset(dx, dy);
+            self.hitbox.offset(dx, dy);
+            if self.hitbox.top_left() == *target {
+                self.path.remove(0);
+            }
+        }
+    }
 }
 
+impl<'a> Collision for Skeleton<'a> {
+    fn doesCollide(&self, hitbox: Rect) -> bool {
+        if self.playerIsTrapped {
+            return self.gateHitBox.has_intersection(hitbox)
+        }
+        false
+    }
+
+    fn overlap(&self, hitbox: Rect) -> Option<Overlap> {
+        if !self.doesCollide(hitbox) {
+            return None;
+        }
+        Some(Overlap{
+            targets: vec![HitTarget::Entity("gate")],
+            penetration: CollisionMod::penetration(hitbox, self.gateHitBox),
+        })
+    }
+}
+
+
 
 
 
 
 
+const SPEED: i32 = 1;
+const REPATH_TIME: u32 = 30;
+const FADE_TIME: u32 = 45;
 
 const SKELETON_SPRITES: &[&str] = &[
-    "Resources/Images/Skeleton_top__half.png",
-    "Resources/Images/Skeleton_bottom__half.png",
-    "Resources/Images/Skeleton_bottom_walk__half.png",
+    "Resources/Images/Skeleton_top.png",
+    "Resources/Images/Skeleton_bottom.png",
+    "Resources/Images/Skeleton_bottom_walk.png",
     "Resources/Images/Gate.png",
 ];
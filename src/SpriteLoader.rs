@@ -1,67 +1,281 @@
 extern crate sdl2;
 
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, Texture, TextureCreator};
-use sdl2::image::LoadTexture;
-use sdl2::video::{WindowContext, Window};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{BlendMode, Canvas, RenderTarget, Texture};
 
+use std::fs;
 use std::ops::Deref;
+use std::path::Path;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-const MIRROR_PATTERN: &'static str = "__half";
+use serde_json::Value;
 
-fn loadSprites<'a, 'b> (creator: &'a TextureCreator<WindowContext>, filenames: &'b [&'b str]) -> Result<Vec<Sprite<'a>>, String> {
+use crate::AnimationLoader::{self, AnimationDef, Condition, FrameSource, ParameterDef, StateDef};
+use crate::AssetMod::Assets;
+use crate::RenderMod::{RenderLayer, RenderQueue, Renderer};
+
+fn loadSprites<'a, 'b> (assets: &Assets<'a>, filenames: &'b [&'b str]) -> Result<Vec<Sprite<'a>>, String> {
     let mut sprites = vec![];
     for filename in filenames {
-        sprites.push(Sprite(creator.load_texture(filename)?, filename.contains(MIRROR_PATTERN)));
+        let texture = assets.texture(filename)?;
+        sprites.push(Sprite{texture, image: Rc::from(*filename), source: None, symmetry: assets.symmetry(filename)});
     }
     Ok(sprites)
 }
 
 pub struct Sprites<'a> {
     sprites: Vec<Sprite<'a>>,
+    names: Vec<String>,
 }
 
 impl<'a> Sprites<'a> {
-    pub fn new<'b> (creator: &'a TextureCreator<WindowContext>, filenames: &'b [&'b str]) -> Result<Sprites<'a>, String> {
+    pub fn new<'b> (assets: &Assets<'a>, filenames: &'b [&'b str]) -> Result<Sprites<'a>, String> {
         Ok(Sprites {
-            sprites: loadSprites(creator, filenames)?,
+            sprites: loadSprites(assets, filenames)?,
+            names: filenames.iter().map(|filename| filename.to_string()).collect(),
         })
     }
 
+    //Cuts a sheet into frameWidth x frameHeight cells, numbered left to right then top to bottom
+    pub fn fromSheet(assets: &Assets<'a>, filename: &str, frameWidth: u32, frameHeight: u32) -> Result<Sprites<'a>, String> {
+        if frameWidth == 0 || frameHeight == 0 {
+            return Err(format!("{}: frames must be at least one pixel", filename));
+        }
+        let texture = assets.texture(filename)?;
+        let query = texture.borrow().query();
+        let columns = query.width / frameWidth;
+        let rows = query.height / frameHeight;
+        if columns == 0 || rows == 0 {
+            return Err(format!("{}: sheet is {}x{}, smaller than a {}x{} frame", filename, query.width, query.height, frameWidth, frameHeight));
+        }
+
+        let symmetry = assets.symmetry(filename);
+        let image: Rc<str> = Rc::from(filename);
+        let mut sprites = vec![];
+        let mut names = vec![];
+        for row in 0..rows {
+            for column in 0..columns {
+                let source = Rect::new((column * frameWidth) as i32, (row * frameHeight) as i32, frameWidth, frameHeight);
+                sprites.push(Sprite{texture: Rc::clone(&texture), image: Rc::clone(&image), source: Some(source), symmetry});
+                names.push(format!("{}", sprites.len() - 1));
+            }
+        }
+        Ok(Sprites{sprites, names})
+    }
+
+    //Loads an atlas described by a JSON manifest in TexturePacker's format:
+    //{"meta": {"image": "sheet.png"}, "frames": [{"filename": "name", "frame": {"x": 0, "y": 0, "w": 50, "h": 50}}, ...]}
+    //frames can also be an object keyed by name. The image path is relative to the manifest.
+    //A frame can set "symmetry", otherwise it has the image's symmetry from the asset manifest.
+    pub fn fromManifest(assets: &Assets<'a>, filename: &str) -> Result<Sprites<'a>, String> {
+        let source = fs::read_to_string(filename)
+            .map_err(|e| format!("{}: could not read manifest: {}", filename, e))?;
+        let manifest: Value = serde_json::from_str(&source)
+            .map_err(|e| format!("{}: invalid JSON: {}", filename, e))?;
+
+        let image = manifest["meta"]["image"].as_str()
+            .ok_or_else(|| format!("{}: missing text field 'meta.image'", filename))?;
+        let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+        let imagePath = directory.join(image).to_string_lossy().into_owned();
+        let texture = assets.texture(&imagePath)?;
+
+        let frames: Vec<(String, &Value)> = match &manifest["frames"] {
+            Value::Array(frames) => frames.iter()
+                .map(|frame| frame["filename"].as_str()
+                    .map(|name| (name.to_string(), frame))
+                    .ok_or_else(|| format!("{}: every frame needs a 'filename'", filename)))
+                .collect::<Result<_, _>>()?,
+            Value::Object(frames) => frames.iter().map(|(name, frame)| (name.clone(), frame)).collect(),
+            _ => return Err(format!("{}: missing list field 'frames'", filename)),
+        };
+
+        let query = texture.borrow().query();
+        let mut sprites = vec![];
+        let mut names = vec![];
+        for (name, frame) in frames {
+            let rect = &frame["frame"];
+            let field = |key: &str| rect[key].as_i64()
+                .ok_or_else(|| format!("{}: frame '{}' is missing '{}'", filename, name, key));
+            let (x, y, w, h) = (field("x")?, field("y")?, field("w")?, field("h")?);
+            if x < 0 || y < 0 || w <= 0 || h <= 0 || x + w > query.width as i64 || y + h > query.height as i64 {
+                return Err(format!("{}: frame '{}' is outside the {}x{} image", filename, name, query.width, query.height));
+            }
+            let source = Rect::new(x as i32, y as i32, w as u32, h as u32);
+            let symmetry = match frame["symmetry"].as_str() {
+                Some(symmetry) => parseSymmetry(symmetry).map_err(|e| format!("{}: frame '{}': {}", filename, name, e))?,
+                None => assets.symmetry(&imagePath),
+            };
+            sprites.push(Sprite{texture: Rc::clone(&texture), image: Rc::from(imagePath.as_str()), source: Some(source), symmetry});
+            names.push(name);
+        }
+        if sprites.is_empty() {
+            return Err(format!("{}: manifest has no frames", filename));
+        }
+        Ok(Sprites{sprites, names})
+    }
+
 /*    #[inline]
     pub fn from_vec(sprites: Vec<Sprite>) -> Sprites {
         Sprites{sprites,}
     }*/
 
-    pub fn getSprite(&self, idx: usize) -> &Sprite {
+    pub fn getSprite(&self, idx: usize) -> &Sprite<'a> {
         &self.sprites[idx]
     }
 
-    /*pub fn len(&self) -> usize {
+    //Sprites are named after their file, their frame in a manifest or their cell number in a sheet
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|other| other == name)
+    }
+
+    pub fn len(&self) -> usize {
         self.sprites.len()
-    }*/
+    }
+
 }
 
 pub struct Animations<'a> {
     animations: Vec<Animation<'a>>,
+    names: Vec<String>,
     activeAnimation: usize,
-    frameCounter: usize,
+    frameCounter: u32, //Ticks since the active animation started
+    shownFrame: Option<usize>,
+    events: Vec<String>,
+    states: Vec<StateDef>, //Without states the animation is only changed by hand
+    activeState: usize,
+    parameterDefs: Vec<ParameterDef>, //The names and values setParameter accepts
+    parameters: HashMap<String, String>,
 }
 
 impl<'a> Animations<'a> {
-    pub fn new(animations: Vec<Animation>) -> Animations {
-        Animations{animations, activeAnimation: 0, frameCounter: 0,}
+    //The first animation starts out active
+    pub fn new(animations: Vec<(String, Animation<'a>)>) -> Animations<'a> {
+        let (names, animations) = animations.into_iter().unzip();
+        Animations{animations, names, activeAnimation: 0, frameCounter: 0, shownFrame: None, events: vec![],
+            states: vec![], activeState: 0, parameterDefs: vec![], parameters: HashMap::new(),}
+    }
+
+    //Loads an animation set, see AnimationLoader for the format
+    pub fn fromFile(assets: &Assets<'a>, filename: &str) -> Result<Animations<'a>, String> {
+        let AnimationLoader::AnimationSetData{animations: definitions, parameters, states} = AnimationLoader::load(filename)?;
+        let mut animations = vec![];
+        for definition in definitions.iter() {
+            let animation = match definition {
+                AnimationDef::Standard{..} => Animation::Standard(
+                    StandardAnimation::fromDef(assets, definition).map_err(|e| format!("{}: {}", filename, e))?
+                ),
+                AnimationDef::Flip{source, ..} => Animation::Flip(FlipAnimation::new(
+                    definitions.iter().position(|other| other.name() == source).unwrap()
+                )),
+            };
+            animations.push((definition.name().to_string(), animation));
+        }
+        let mut animations = Animations::new(animations);
+        animations.states = states;
+        for parameter in parameters.iter() {
+            animations.parameters.insert(parameter.name.clone(), parameter.values[0].clone());
+        }
+        animations.parameterDefs = parameters;
+        Ok(animations)
+    }
+
+    //Parameters pick the state machine's transitions and fill in its animation names.
+    //Only the parameters and values declared in the animation file can be set.
+    pub fn setParameter(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let parameter = self.parameterDefs.iter().find(|parameter| parameter.name == name)
+            .ok_or_else(|| format!("unknown parameter '{}'", name))?;
+        if !parameter.values.iter().any(|allowed| allowed == value) {
+            return Err(format!("'{}' is not a value of parameter '{}'", value, name));
+        }
+        self.parameters.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn state(&self) -> &str {
+        self.states.get(self.activeState).map_or("", |state| state.name.as_str())
+    }
+
+    //Takes the first transition out of the current state whose conditions all hold,
+    //then plays the state's animation for the current parameters
+    fn updateState(&mut self) -> Result<(), String> {
+        if self.states.is_empty() {
+            return Ok(());
+        }
+        let finished = self.isFinished();
+        let state = &self.states[self.activeState];
+        let transition = state.transitions.iter().find(|transition| transition.conditions.iter().all(|condition| match condition {
+            Condition::Equals(name, value) => self.parameters.get(name) == Some(value),
+            Condition::NotEquals(name, value) => self.parameters.get(name) != Some(value),
+            Condition::Finished => finished,
+        }));
+        let mut restart = false;
+        if let Some(transition) = transition {
+            self.activeState = self.states.iter().position(|state| state.name == transition.target).unwrap();
+            restart = true;
+        }
+
+        let mut animation = self.states[self.activeState].animation.clone();
+        for (name, value) in self.parameters.iter() {
+            animation = animation.replace(&format!("{{{}}}", name), value);
+        }
+        self.changeAnimation(&animation).map_err(|e| format!("state '{}': {}", self.state(), e))?;
+        //Entering a state always plays its animation from the start, even when it was already showing
+        if restart {
+            self.frameCounter = 0;
+            self.shownFrame = None;
+        }
+        Ok(())
     }
 
     /*pub fn getAnimation(&self, idx: usize) -> &Animation {
         &self.animations[idx]
     }*/
 
-    pub fn update(&mut self) {
-        self.frameCounter = (self.frameCounter + 1) % usize::max_value();
+    pub fn update(&mut self) -> Result<(), String> {
+        self.frameCounter = self.frameCounter.saturating_add(1);
+        self.updateState()?;
+
+        let (animation, _) = self.getStandard();
+        let frame = animation.frameAt(self.frameCounter);
+        let events = if self.shownFrame != Some(frame) && !animation.isFinishedAt(self.frameCounter) {
+            animation.events[frame].clone()
+        }
+        else {
+            vec![]
+        };
+        self.shownFrame = Some(frame);
+        self.events = events;
+        Ok(())
+    }
+
+    //The events of the frame the last update moved onto
+    pub fn events(&self) -> &[String] {
+        &self.events
+    }
+
+    //Whether a Once or HoldLast animation has played all its frames
+    pub fn isFinished(&self) -> bool {
+        self.getStandard().0.isFinishedAt(self.frameCounter)
+    }
+
+    //The animation to play and whether to mirror it
+    fn getStandard(&self) -> (&StandardAnimation<'a>, bool) {
+        match self.getAnimation() {
+            Animation::Standard(animation) => (animation, false),
+            Animation::Flip(animation) =>
+                if let Animation::Standard(animation) = &self.animations[animation.getIndex()] {
+                    (animation, true)
+                }
+                else {
+                    panic!("Flipped animation is a flip of a flipped animation");
+                },
+        }
     }
 
-    fn getAnimation<'b>(&'b self) -> &Animation<'b> {
+    fn getAnimation(&self) -> &Animation<'a> {
         &self.animations[self.activeAnimation]
         /*match &self.animations[self.activeAnimation] {
             Animation::Standard(animation) => animation.getFrame(self.frameCounter),
@@ -75,32 +289,31 @@ impl<'a> Animations<'a> {
         }//.getFrame(self.frameCounter)*/
     }
 
-    pub fn drawNextFrame(&self, canvas: &mut Canvas<Window>, position: Rect) {
-        match self.getAnimation() {
-
-            Animation::Standard(animation) => 
-                animation.getFrame(self.frameCounter)
-                .draw(canvas, position, false, false),
-
-            Animation::Flip(animation) => 
-                if let Animation::Standard(animation) = &self.animations[animation.getIndex()] {
-                    animation.getFrame(self.frameCounter)
-                    .draw(canvas, position, true, false);
-                }
-                else {
-                    panic!("Flipped animation is a flip of a flipped animation");
-                },
+    pub fn queueNextFrame(&self, queue: &mut RenderQueue<'a>, key: i32, position: Rect, params: &DrawParams) {
+        let (animation, flipped) = self.getStandard();
+        let params = DrawParams{flipHorizontal: params.flipHorizontal != flipped, ..*params};
+        queue.push(RenderLayer::Entities, key, animation.getFrameAt(self.frameCounter), position, &params);
+    }
 
-        }//.draw(canvas, position, false, false);
-    } 
+    //How far through the active animation playback is, from 0 to 1
+    pub fn progress(&self) -> f64 {
+        let length = self.getStandard().0.length();
+        (self.frameCounter as f64 / length as f64).min(1f64)
+    }
 
-    pub fn changeAnimation(&mut self, idx: usize) -> Result<(), &'static str> {
-        if idx >= self.animations.len() {
-            return Err("Out of range");
+    pub fn changeAnimation(&mut self, name: &str) -> Result<(), String> {
+        let idx = match self.names.iter().position(|other| other == name) {
+            Some(idx) => idx,
+            None => return Err(format!("No animation named '{}'", name)),
+        };
+
+        //Switching animations starts the new one from its first frame
+        if idx != self.activeAnimation {
+            self.activeAnimation = idx;
+            self.frameCounter = 0;
+            self.shownFrame = None;
         }
 
-        self.activeAnimation = idx;
-
         Ok(())
     }
 
@@ -143,13 +356,25 @@ impl FlipAnimation {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Playback {
+    Once, //Plays through, then goes back to the first frame
+    Loop,
+    PingPong, //Plays forwards, then backwards, and repeats
+    HoldLast, //Plays through, then stays on the last frame
+}
+
 pub struct StandardAnimation<'a> {
     sprites: Sprites<'a>,
     frames: Vec<usize>,
+    durations: Vec<u32>,
+    events: Vec<Vec<String>>, //Reported by Animations when playback reaches the frame
+    playback: Playback,
 }
 
 impl<'a> StandardAnimation<'a> {
-    /*pub fn fromSprites<'b>(sprites: Sprites<'a>, positions: &'b [usize]) -> Result<Animation<'a>, String> {
+    //Builds an animation out of already loaded sprites, such as the frames of a sheet
+    pub fn fromSprites<'b>(sprites: Sprites<'a>, positions: &'b [usize]) -> Result<StandardAnimation<'a>, String> {
         let length = sprites.len();
 
         let mut frames = vec![];
@@ -162,31 +387,138 @@ impl<'a> StandardAnimation<'a> {
                 return Err("Frame out of sprite bounds".to_string());
             }
         }
-        
-        Ok(Animation{sprites, frames,})
-    }*/
-    pub fn fromFiles<'b> (creator: &'a TextureCreator<WindowContext>, filenames: &'b [&'b str], positions: &'b [usize]) -> Result<StandardAnimation<'a>, String> {
-        let length = filenames.len();
-        
-        let mut frames = vec![];
+        if frames.is_empty() {
+            return Err("Animation has no frames".to_string());
+        }
+        let durations = vec![1; frames.len()];
+        let events = vec![vec![]; frames.len()];
+        Ok(StandardAnimation{sprites, frames, durations, events, playback: Playback::Loop,})
+    }
+    pub fn fromFiles<'b> (assets: &Assets<'a>, filenames: &'b [&'b str], positions: &'b [usize]) -> Result<StandardAnimation<'a>, String> {
+        StandardAnimation::fromSprites(Sprites::new(assets, filenames)?, positions)
+    }
 
-        for position in positions {
-            if length > *position {
-                frames.push(*position);
+    fn fromDef(assets: &Assets<'a>, definition: &AnimationDef) -> Result<StandardAnimation<'a>, String> {
+        let (name, playback, source, frames) = match definition {
+            AnimationDef::Standard{name, playback, source, frames} => (name, playback, source, frames),
+            AnimationDef::Flip{..} => return Err("Flipped animations have no frames of their own".to_string()),
+        };
+
+        let sprites = match source {
+            FrameSource::Files => {
+                let mut filenames: Vec<&str> = vec![];
+                for frame in frames.iter() {
+                    if !filenames.contains(&frame.image.as_str()) {
+                        filenames.push(&frame.image);
+                    }
+                }
+                Sprites::new(assets, &filenames)?
+            },
+            FrameSource::Sheet{image, frameWidth, frameHeight} => Sprites::fromSheet(assets, image, *frameWidth, *frameHeight)?,
+            FrameSource::Manifest(manifest) => Sprites::fromManifest(assets, manifest)?,
+        };
+
+        let mut positions = vec![];
+        for frame in frames.iter() {
+            match sprites.find(&frame.image) {
+                Some(position) => positions.push(position),
+                None => return Err(format!("animation '{}': no frame '{}' in its sheet", name, frame.image)),
             }
-            else {
-                return Err("Frame out of sprite bounds".to_string());
+        }
+
+        let mut animation = StandardAnimation::fromSprites(sprites, &positions)?;
+        animation.durations = frames.iter().map(|frame| frame.duration).collect();
+        for (position, frame) in frames.iter().enumerate() {
+            for event in frame.events.iter() {
+                animation.addEvent(position, event)?;
             }
         }
-        Ok(StandardAnimation{sprites: Sprites::new(creator, filenames)?, frames,})
+        animation.setPlayback(*playback);
+        Ok(animation)
+    }
+
+    //Like fromFiles, but each frame stays on screen for its own number of ticks
+    pub fn fromFilesTimed<'b> (assets: &Assets<'a>, filenames: &'b [&'b str], positions: &'b [usize], durations: &'b [u32]) -> Result<StandardAnimation<'a>, String> {
+        if positions.len() != durations.len() {
+            return Err("Every frame needs a duration".to_string());
+        }
+        if durations.contains(&0) {
+            return Err("Frame durations must be at least one tick".to_string());
+        }
+        let mut animation = StandardAnimation::fromFiles(assets, filenames, positions)?;
+        animation.durations = durations.to_vec();
+        Ok(animation)
     }
 
     /*pub fn duration(&self) -> usize {
         self.frames.len()
     }*/
 
-    pub fn getFrame(&self, counter: usize) -> &Sprite {
-        self.getSprite(self.frames[counter % self.frames.len()])
+    pub fn setPlayback(&mut self, playback: Playback) {
+        self.playback = playback;
+    }
+
+    //Names something that happens when the frame comes up, like "hit_start" or "footstep"
+    pub fn addEvent(&mut self, frame: usize, name: &str) -> Result<(), String> {
+        match self.events.get_mut(frame) {
+            Some(events) => {
+                events.push(name.to_string());
+                Ok(())
+            },
+            None => Err(format!("Event '{}' is on frame {}, but the animation has {} frames", name, frame, self.frames.len())),
+        }
+    }
+
+    //Ticks it takes to play every frame once
+    pub fn length(&self) -> u32 {
+        self.durations.iter().sum()
+    }
+
+    pub fn isFinishedAt(&self, time: u32) -> bool {
+        match self.playback {
+            Playback::Once | Playback::HoldLast => time >= self.length(),
+            Playback::Loop | Playback::PingPong => false,
+        }
+    }
+
+    //Time is in ticks since the animation started
+    pub fn getFrameAt(&self, time: u32) -> &Sprite<'a> {
+        self.getSprite(self.frames[self.frameAt(time)])
+    }
+
+    fn frameAt(&self, time: u32) -> usize {
+        let length = self.length();
+        let last = self.frames.len() - 1;
+        let time = match self.playback {
+            Playback::Loop => time % length,
+            Playback::Once => if time >= length {0} else {time},
+            Playback::HoldLast => if time >= length {return last} else {time},
+            Playback::PingPong => {
+                //The way back skips the first and last frames so they aren't shown twice in a row
+                let back: u32 = self.durations.iter().skip(1).take(last.saturating_sub(1)).sum();
+                let time = time % (length + back);
+                if time >= length {
+                    let mut time = time - length;
+                    for frame in (1..last).rev() {
+                        if time < self.durations[frame] {
+                            return frame;
+                        }
+                        time -= self.durations[frame];
+                    }
+                    return 0;
+                }
+                time
+            },
+        };
+
+        let mut time = time;
+        for (frame, duration) in self.durations.iter().enumerate() {
+            if time < *duration {
+                return frame;
+            }
+            time -= duration;
+        }
+        last
     }
     
 }
@@ -209,20 +541,126 @@ impl<'a> Deref for StandardAnimation<'a> {
 
 }*/
 
-pub type Mirror = bool;
-pub struct Sprite<'a> (Texture<'a>, Mirror);
+//Symmetric sprites only store one part of the picture and mirror it to fill in the rest
+#[derive(Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    None,
+    Horizontal, //The image is the right half
+    Vertical, //The image is the bottom half
+    Quad, //The image is the bottom right quarter
+}
+
+pub fn parseSymmetry(word: &str) -> Result<Symmetry, String> {
+    match word {
+        "none" => Ok(Symmetry::None),
+        "horizontal" => Ok(Symmetry::Horizontal),
+        "vertical" => Ok(Symmetry::Vertical),
+        "quad" => Ok(Symmetry::Quad),
+        other => Err(format!("unknown symmetry '{}', expected none, horizontal, vertical or quad", other)),
+    }
+}
+
+//How to draw a sprite beyond where it goes
+#[derive(Clone, Copy)]
+pub struct DrawParams {
+    pub angle: f64, //Degrees clockwise
+    pub pivot: Option<Point>, //What to rotate about, relative to the top left of the quad. None is its centre.
+    pub flipHorizontal: bool,
+    pub flipVertical: bool,
+    pub alpha: u8,
+    pub tint: Color, //Multiplies the sprite's colours, white leaves them alone
+    pub additive: bool, //Adds the sprite's colours to what is underneath, for glows and flashes
+}
+
+impl Default for DrawParams {
+    fn default() -> DrawParams {
+        DrawParams{angle: 0f64, pivot: None, flipHorizontal: false, flipVertical: false, alpha: 255, tint: Color::WHITE, additive: false}
+    }
+}
+
+//A sprite draws source out of its texture, or the whole texture when source is None.
+//Sprites cut from the same sheet share one texture, which hot reloading can swap out from under them.
+#[derive(Clone)]
+pub struct Sprite<'a> {
+    texture: Rc<RefCell<Texture<'a>>>,
+    image: Rc<str>, //The file the texture came from
+    source: Option<Rect>,
+    symmetry: Symmetry,
+}
 
 impl<'a> Sprite<'a> {
-    pub fn draw(&self, canvas: &mut Canvas<Window>, quad: Rect, flipHorizontal: bool, flipVertical: bool) {
-        if self.1 {
-            let mut quad = quad;
-            quad.w = (0.5 * quad.w as f32) as i32;
-            canvas.copy_ex(&self.0, None, quad, 0f64, None, true, false); //Render the left half
-            quad.x += quad.w;
-            canvas.copy(&self.0, None, quad); //Render the right half
+    pub fn drawWith(&self, canvas: &mut dyn Renderer, quad: Rect, params: &DrawParams) {
+        canvas.drawSprite(self, quad, params);
+    }
+
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+
+    pub fn source(&self) -> Option<Rect> {
+        self.source
+    }
+
+    //Does the actual drawing for SDL renderers
+    pub fn copyTo<T: RenderTarget>(&self, canvas: &mut Canvas<T>, quad: Rect, params: &DrawParams) {
+        let mut texture = self.texture.borrow_mut();
+        //The texture is shared with other sprites, so put its settings back afterwards
+        let modulated = params.alpha != 255 || params.tint != Color::WHITE || params.additive;
+        let previous = (texture.color_mod(), texture.alpha_mod(), texture.blend_mode());
+        if modulated {
+            texture.set_color_mod(params.tint.r, params.tint.g, params.tint.b);
+            texture.set_alpha_mod(params.alpha);
+            texture.set_blend_mode(if params.additive {BlendMode::Add} else {BlendMode::Blend});
         }
-        else {
-            canvas.copy_ex(&self.0, None, quad, 0f64, None, flipHorizontal, flipVertical);
+
+        let DrawParams{angle, flipHorizontal, flipVertical, ..} = *params;
+        let pivot = match params.pivot {
+            Some(pivot) => quad.top_left() + pivot,
+            None => quad.center(),
+        };
+        let (columns, rows) = match self.symmetry {
+            Symmetry::None => (1, 1),
+            Symmetry::Horizontal => (2, 1),
+            Symmetry::Vertical => (1, 2),
+            Symmetry::Quad => (2, 2),
+        };
+        let width = quad.width() / columns;
+        let height = quad.height() / rows;
+        for row in 0..rows {
+            for column in 0..columns {
+                //Every piece but the bottom right one is a mirror of the image
+                let mirrorHorizontal = column + 1 < columns;
+                let mirrorVertical = row + 1 < rows;
+                //Flipping the whole sprite moves each piece to the other side and flips it too
+                let x = if flipHorizontal {columns - 1 - column} else {column};
+                let y = if flipVertical {rows - 1 - row} else {row};
+                let piece = Rect::new(quad.x() + (x * width) as i32, quad.y() + (y * height) as i32, width, height);
+                //Every piece turns about the same point so the sprite rotates as a whole
+                canvas.copy_ex(&texture, self.source, piece, angle, pivot - piece.top_left(),
+                    mirrorHorizontal != flipHorizontal, mirrorVertical != flipVertical);
+            }
+        }
+
+        if modulated {
+            let ((r, g, b), alpha, blend) = previous;
+            texture.set_color_mod(r, g, b);
+            texture.set_alpha_mod(alpha);
+            texture.set_blend_mode(blend);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn onlyDeclaredParametersCanBeSet() {
+        let mut animations = Animations::new(vec![]);
+        animations.parameterDefs = vec![ParameterDef{name: "direction".to_string(), values: vec!["down".to_string(), "up".to_string()]}];
+        assert_eq!(animations.setParameter("direction", "up"), Ok(()));
+        assert_eq!(animations.setParameter("direction", "sideways"), Err("'sideways' is not a value of parameter 'direction'".to_string()));
+        assert_eq!(animations.setParameter("speed", "up"), Err("unknown parameter 'speed'".to_string()));
+        assert_eq!(animations.parameters["direction"], "up");
+    }
+}
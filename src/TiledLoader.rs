@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::AssetMod::resolve;
+use crate::MapLoader::{self, Entrance, Exit, LayerData, LayerKind, MapData, Spawn, TileDef, TileFrame, TriggerArea};
+use crate::MapMod::{CollisionType, TILE_SIZE};
+
+//Tiled stores flips and rotations in the top bits of each tile id
+const FLIP_FLAGS: u64 = 0xF000_0000;
+
+pub fn load(filename: &str) -> Result<MapData, String> {
+    let source = fs::read_to_string(filename)
+        .map_err(|e| format!("{}: could not read map file: {}", filename, e))?;
+    let directory = Path::new(filename).parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, directory).map_err(|e| format!("{}: {}", filename, e))
+}
+
+//Image paths in the map are resolved relative to directory, like Tiled does
+pub fn parse(source: &str, directory: &Path) -> Result<MapData, String> {
+    let map: Value = serde_json::from_str(source).map_err(|e| format!("invalid JSON: {}", e))?;
+
+    let orientation = getStr(&map, "orientation")?;
+    if orientation != "orthogonal" {
+        return Err(format!("only orthogonal maps are supported, found {}", orientation));
+    }
+    if map["infinite"].as_bool() == Some(true) {
+        return Err("infinite maps are not supported".to_string());
+    }
+    let tileWidth = getNumber(&map, "tilewidth")? as u32;
+    let tileHeight = getNumber(&map, "tileheight")? as u32;
+    if tileWidth != TILE_SIZE || tileHeight != TILE_SIZE {
+        return Err(format!("tiles must be {}x{} pixels, found {}x{}", TILE_SIZE, TILE_SIZE, tileWidth, tileHeight));
+    }
+    let width = getNumber(&map, "width")? as usize;
+    let height = getNumber(&map, "height")? as usize;
+    if width == 0 || height == 0 {
+        return Err("map has no tiles".to_string());
+    }
+
+    let mut tiles = HashMap::new();
+    for tileset in getArray(&map, "tilesets")? {
+        let firstGid = getNumber(tileset, "firstgid")? as u64;
+        match tileset["source"].as_str() {
+            Some(source) => loadExternalTileset(&directory.join(source), firstGid, &mut tiles)?,
+            None => loadTileset(tileset, directory, firstGid, &mut tiles)?,
+        }
+    }
+
+    let mut layers = vec![];
+    let mut spawns = vec![];
+    let mut triggers = vec![];
+    let mut entrances = vec![];
+    let mut exits = vec![];
+    for layer in getArray(&map, "layers")? {
+        let name = layer["name"].as_str().unwrap_or("");
+        match getStr(layer, "type")? {
+            "tilelayer" => layers.push(
+                tileLayer(layer, width, height, &tiles).map_err(|e| format!("layer '{}': {}", name, e))?
+            ),
+            "objectgroup" => {
+                let objects = Objects{spawns: &mut spawns, triggers: &mut triggers, entrances: &mut entrances, exits: &mut exits};
+                objectLayer(layer, directory, objects).map_err(|e| format!("layer '{}': {}", name, e))?
+            },
+            other => return Err(format!("layer '{}': {} layers are not supported", name, other)),
+        }
+    }
+    if layers.is_empty() {
+        return Err("map has no tile layers".to_string());
+    }
+
+    let outside = match property(&map, "outside") {
+        Some(outside) => {
+            let words: Vec<&str> = outside.split_whitespace().collect();
+            MapLoader::parseCollision(&words).map_err(|e| format!("map property 'outside': {}", e))?
+        },
+        None => CollisionType::Block,
+    };
+
+    Ok(MapData{layers, spawns, triggers, entrances, exits, outside})
+}
+
+fn loadExternalTileset(path: &Path, firstGid: u64, tiles: &mut HashMap<u64, TileDef>) -> Result<(), String> {
+    let filename = path.to_string_lossy();
+    if filename.ends_with(".tsx") {
+        return Err(format!("tileset {} is in Tiled's XML format, export it as JSON instead", filename));
+    }
+    let source = fs::read_to_string(path)
+        .map_err(|e| format!("could not read tileset {}: {}", filename, e))?;
+    let tileset: Value = serde_json::from_str(&source)
+        .map_err(|e| format!("tileset {} is not valid JSON: {}", filename, e))?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    loadTileset(&tileset, directory, firstGid, tiles).map_err(|e| format!("{}: {}", filename, e))
+}
+
+fn loadTileset(tileset: &Value, directory: &Path, firstGid: u64, tiles: &mut HashMap<u64, TileDef>) -> Result<(), String> {
+    let name = tileset["name"].as_str().unwrap_or("");
+    if tileset["image"].is_string() {
+        return Err(format!("tileset '{}' is a single spritesheet, only image collection tilesets are supported", name));
+    }
+
+    let mut images = HashMap::new();
+    for tile in getArray(tileset, "tiles")? {
+        images.insert(getNumber(tile, "id")? as u64, resolve(directory, getStr(tile, "image")?));
+    }
+
+    for tile in getArray(tileset, "tiles")? {
+        let id = getNumber(tile, "id")? as u64;
+        let image = images[&id].clone();
+        let edge = property(tile, "edge").map(|edge| resolve(directory, &edge));
+        let corner = property(tile, "corner").map(|corner| resolve(directory, &corner));
+        let collision = match property(tile, "collision") {
+            Some(collision) => {
+                let words: Vec<&str> = collision.split_whitespace().collect();
+                MapLoader::parseCollision(&words).map_err(|e| format!("tileset '{}', tile {}: {}", name, id, e))?
+            },
+            None => CollisionType::None,
+        };
+
+        let mut frames = vec![];
+        if let Some(animation) = tile["animation"].as_array() {
+            for frame in animation {
+                let frameId = getNumber(frame, "tileid")? as u64;
+                let image = images.get(&frameId)
+                    .ok_or_else(|| format!("tileset '{}', tile {}: animation uses missing tile {}", name, id, frameId))?;
+                //Tiled times frames in milliseconds, the game runs at 60 ticks a second
+                let duration = ((getNumber(frame, "duration")? * 60f64 / 1000f64).round() as u32).max(1);
+                frames.push(TileFrame{image: image.clone(), duration});
+            }
+        }
+
+        tiles.insert(firstGid + id, TileDef{image, frames, edge, corner, collision});
+    }
+    Ok(())
+}
+
+fn tileLayer(layer: &Value, width: usize, height: usize, tiles: &HashMap<u64, TileDef>) -> Result<LayerData, String> {
+    if layer["chunks"].is_array() {
+        return Err("chunked layers are not supported".to_string());
+    }
+    if let Some(encoding) = layer["encoding"].as_str() {
+        if encoding != "csv" {
+            return Err(format!("{} tile data is not supported, save the map with CSV layer format", encoding));
+        }
+    }
+
+    let kind = match property(layer, "kind").unwrap_or_else(|| getStr(layer, "name").unwrap_or("").to_lowercase()).as_str() {
+        "background" => LayerKind::Background,
+        "foreground" => LayerKind::Foreground,
+        _ => LayerKind::Collision,
+    };
+
+    let data = getArray(layer, "data")?;
+    if data.len() != width * height {
+        return Err(format!("expected {} tiles, found {}", width * height, data.len()));
+    }
+
+    //Each layer only keeps the tiles it uses, in the order they first appear
+    let mut tileset = vec![];
+    let mut localIds: HashMap<u64, usize> = HashMap::new();
+    let mut grid = vec![];
+    for row in data.chunks(width) {
+        let mut cells = vec![];
+        for gid in row {
+            let gid = gid.as_u64().ok_or_else(|| "tile ids must be numbers".to_string())?;
+            if gid & FLIP_FLAGS != 0 {
+                return Err("flipped or rotated tiles are not supported".to_string());
+            }
+            if gid == 0 {
+                cells.push(None);
+                continue;
+            }
+            let idx = match localIds.get(&gid) {
+                Some(idx) => *idx,
+                None => {
+                    let tile = tiles.get(&gid).ok_or_else(|| format!("tile id {} is not in any tileset", gid))?;
+                    tileset.push(tile.clone());
+                    localIds.insert(gid, tileset.len() - 1);
+                    tileset.len() - 1
+                },
+            };
+            cells.push(Some(idx));
+        }
+        grid.push(cells);
+    }
+
+    Ok(LayerData{kind, tileset, tiles: grid})
+}
+
+struct Objects<'a> {
+    spawns: &'a mut Vec<Spawn>,
+    triggers: &'a mut Vec<TriggerArea>,
+    entrances: &'a mut Vec<Entrance>,
+    exits: &'a mut Vec<Exit>,
+}
+
+fn objectLayer(layer: &Value, directory: &Path, objects: Objects) -> Result<(), String> {
+    for object in getArray(layer, "objects")? {
+        let name = getStr(object, "name")?;
+        //Tiled 1.9 renamed an object's type to class
+        let kind = object["type"].as_str().filter(|kind| !kind.is_empty())
+            .or_else(|| object["class"].as_str())
+            .unwrap_or("");
+        let x = getNumber(object, "x")?.round() as i32;
+        let y = getNumber(object, "y")?.round() as i32;
+
+        match kind {
+            "spawn" => objects.spawns.push(Spawn{kind: name.to_string(), x, y}),
+            "entrance" => objects.entrances.push(Entrance{name: name.to_string(), x, y}),
+            "trigger" => {
+                let (width, height) = rectangle(object, name)?;
+                objects.triggers.push(TriggerArea{name: name.to_string(), x, y, width, height});
+            },
+            "exit" => {
+                let (width, height) = rectangle(object, name)?;
+                let map = property(object, "map")
+                    .ok_or_else(|| format!("exit '{}' needs a 'map' property", name))?;
+                let entrance = property(object, "entrance")
+                    .ok_or_else(|| format!("exit '{}' needs an 'entrance' property", name))?;
+                objects.exits.push(Exit{x, y, width, height, map: resolve(directory, &map), entrance});
+            },
+            other => return Err(format!("object '{}' has type '{}', expected spawn, entrance, trigger or exit", name, other)),
+        }
+    }
+    Ok(())
+}
+
+fn rectangle(object: &Value, name: &str) -> Result<(u32, u32), String> {
+    for (shape, description) in &[("ellipse", "an ellipse"), ("point", "a point"), ("polygon", "a polygon"), ("polyline", "a polyline"), ("text", "text")] {
+        if !object[*shape].is_null() {
+            return Err(format!("'{}' is {}, only rectangles are supported", name, description));
+        }
+    }
+    let width = getNumber(object, "width")?.round() as u32;
+    let height = getNumber(object, "height")?.round() as u32;
+    if width == 0 || height == 0 {
+        return Err(format!("'{}' has no area", name));
+    }
+    Ok((width, height))
+}
+
+fn property(value: &Value, name: &str) -> Option<String> {
+    value["properties"].as_array()?
+        .iter()
+        .find(|property| property["name"].as_str() == Some(name))
+        .and_then(|property| property["value"].as_str())
+        .map(|value| value.to_string())
+}
+
+fn getStr<'a>(value: &'a Value, key: &str) -> Result<&'a str, String> {
+    value[key].as_str().ok_or_else(|| format!("missing text field '{}'", key))
+}
+
+fn getNumber(value: &Value, key: &str) -> Result<f64, String> {
+    value[key].as_f64().ok_or_else(|| format!("missing number field '{}'", key))
+}
+
+fn getArray<'a>(value: &'a Value, key: &str) -> Result<&'a Vec<Value>, String> {
+    value[key].as_array().ok_or_else(|| format!("missing list field '{}'", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A 2x2 map with a wall, an animated tile and one of each object
+    fn map() -> Value {
+        serde_json::from_str(r#"{
+            "orientation": "orthogonal", "infinite": false, "tilewidth": 50, "tileheight": 50, "width": 2, "height": 2,
+            "properties": [{"name": "outside", "type": "string", "value": "none"}],
+            "tilesets": [{"firstgid": 1, "name": "ground", "tiles": [
+                {"id": 0, "image": "../Images/Ground.png", "properties": [{"name": "edge", "type": "string", "value": "../Images/Ground_edge.png"}]},
+                {"id": 1, "image": "../Images/Wall.png", "properties": [{"name": "collision", "type": "string", "value": "block"}]},
+                {"id": 2, "image": "../Images/Water_0.png", "animation": [{"tileid": 2, "duration": 500}, {"tileid": 3, "duration": 250}]},
+                {"id": 3, "image": "../Images/Water_1.png"}
+            ]}],
+            "layers": [
+                {"type": "tilelayer", "name": "Ground", "data": [1, 2, 0, 3]},
+                {"type": "objectgroup", "name": "Objects", "objects": [
+                    {"name": "player", "type": "spawn", "x": 50, "y": 50},
+                    {"name": "trap", "class": "trigger", "x": 0, "y": 0, "width": 50, "height": 50},
+                    {"name": "west", "type": "entrance", "x": 0, "y": 50},
+                    {"name": "door", "type": "exit", "x": 50, "y": 0, "width": 50, "height": 50, "properties": [
+                        {"name": "map", "type": "string", "value": "Level2.json"},
+                        {"name": "entrance", "type": "string", "value": "east"}
+                    ]}
+                ]}
+            ]
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn imageCollectionMap() {
+        let map = parse(&map().to_string(), Path::new("dir")).unwrap();
+        assert!(map.outside == CollisionType::None);
+
+        let layer = &map.layers[0];
+        assert!(layer.kind == LayerKind::Collision);
+        assert_eq!(layer.tiles, vec![vec![Some(0), Some(1)], vec![None, Some(2)]]);
+        assert_eq!(layer.tileset[0].image, "Images/Ground.png");
+        assert_eq!(layer.tileset[0].edge.as_deref(), Some("Images/Ground_edge.png"));
+        assert!(layer.tileset[1].collision == CollisionType::Block);
+        let frames: Vec<(&str, u32)> = layer.tileset[2].frames.iter().map(|frame| (frame.image.as_str(), frame.duration)).collect();
+        assert_eq!(frames, vec![("Images/Water_0.png", 30), ("Images/Water_1.png", 15)]);
+
+        assert_eq!(map.findSpawn("player").map(|spawn| (spawn.x, spawn.y)), Some((50, 50)));
+        assert_eq!(map.triggers[0].name, "trap");
+        assert_eq!(map.findEntrance("west").map(|entrance| (entrance.x, entrance.y)), Some((0, 50)));
+        assert_eq!(map.exits[0].map, "dir/Level2.json");
+        assert_eq!(map.exits[0].entrance, "east");
+    }
+
+    #[test]
+    fn infiniteMap() {
+        let mut map = map();
+        map["infinite"] = Value::from(true);
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "infinite maps are not supported");
+    }
+
+    #[test]
+    fn base64Layer() {
+        let mut map = map();
+        map["layers"][0]["encoding"] = Value::from("base64");
+        map["layers"][0]["data"] = Value::from("AQAAAAIAAAAAAAAAAwAAAA==");
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "layer 'Ground': base64 tile data is not supported, save the map with CSV layer format");
+    }
+
+    #[test]
+    fn flippedTile() {
+        let mut map = map();
+        map["layers"][0]["data"][1] = Value::from(0x8000_0002u64);
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "layer 'Ground': flipped or rotated tiles are not supported");
+    }
+
+    #[test]
+    fn xmlTileset() {
+        let mut map = map();
+        map["tilesets"][0] = serde_json::json!({"firstgid": 1, "source": "ground.tsx"});
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "tileset dir/ground.tsx is in Tiled's XML format, export it as JSON instead");
+    }
+
+    #[test]
+    fn spritesheetTileset() {
+        let mut map = map();
+        map["tilesets"][0]["image"] = Value::from("sheet.png");
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "tileset 'ground' is a single spritesheet, only image collection tilesets are supported");
+    }
+
+    #[test]
+    fn ellipseTrigger() {
+        let mut map = map();
+        map["layers"][1]["objects"][1]["ellipse"] = Value::from(true);
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "layer 'Objects': 'trap' is an ellipse, only rectangles are supported");
+    }
+
+    #[test]
+    fn exitWithoutMap() {
+        let mut map = map();
+        map["layers"][1]["objects"][3]["properties"] = serde_json::json!([{"name": "entrance", "type": "string", "value": "east"}]);
+        assert_eq!(parse(&map.to_string(), Path::new("dir")).unwrap_err(), "layer 'Objects': exit 'door' needs a 'map' property");
+    }
+}
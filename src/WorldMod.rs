@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::mem;
+use std::time::SystemTime;
+
+use crate::AssetMod::{self, Assets};
+use crate::MapLoader::{self, MapData};
+use crate::MapMod::Map;
+use crate::PlayerMod::Player;
+use crate::SkeletonMod::{Skeleton, SkeletonState};
+
+pub struct Room<'a> {
+    pub map: Map<'a>,
+    pub skeletons: Vec<Skeleton<'a>>,
+}
+
+impl<'a> Room<'a> {
+    fn load(assets: &Assets<'a>, path: &str, data: &MapData) -> Result<Room<'a>, String> {
+        let map = Map::new(data, assets).map_err(|e| format!("{}: {}", path, e))?;
+
+        //Each skeleton guards the gate spawned in the same order
+        let gates: Vec<(i32, i32)> = data.spawns.iter()
+            .filter(|spawn| spawn.kind == "gate")
+            .map(|spawn| (spawn.x, spawn.y))
+            .collect();
+        let mut skeletons = vec![];
+        for spawn in data.spawns.iter().filter(|spawn| spawn.kind == "skeleton") {
+            let gate = match gates.get(skeletons.len()) {
+                Some(gate) => *gate,
+                None => return Err(format!("{}: skeleton {} has no gate spawn", path, skeletons.len() + 1)),
+            };
+            skeletons.push(Skeleton::new(assets, spawn.x, spawn.y, gate)?);
+        }
+
+        Ok(Room{map, skeletons})
+    }
+}
+
+pub struct World<'a, 'b> {
+    assets: &'b Assets<'a>,
+    path: String,
+    room: Room<'a>,
+    modified: Option<SystemTime>, //When the current room's map file was last changed
+    visited: HashMap<String, Vec<SkeletonState>>,
+}
+
+impl<'a, 'b> World<'a, 'b> {
+    //Loads the first room and returns it along with where the player starts
+    pub fn new(assets: &'b Assets<'a>, path: &str) -> Result<(World<'a, 'b>, (i32, i32)), String> {
+        let modified = AssetMod::modifiedTime(path);
+        let data = MapLoader::load(path)?;
+        let start = data.findSpawn("player").map_or((50, 50), |spawn| (spawn.x, spawn.y));
+        let room = Room::load(assets, path, &data)?;
+        Ok((World{assets, path: AssetMod::normalizePath(path), room, modified, visited: HashMap::new()}, start))
+    }
+
+    pub fn room(&mut self) -> &mut Room<'a> {
+        &mut self.room
+    }
+
+    //Moves the player through the exit they are standing in, if any. Returns whether the room changed.
+    pub fn checkExits(&mut self, player: &mut Player) -> Result<bool, String> {
+        let exit = match self.room.map.exitAt(player.hitbox()) {
+            Some(exit) => exit.clone(),
+            None => return Ok(false),
+        };
+
+        let modified = AssetMod::modifiedTime(&exit.map);
+        let data = MapLoader::load(&exit.map)?;
+        let entrance = data.findEntrance(&exit.entrance)
+            .ok_or_else(|| format!("{}: no entrance named '{}'", exit.map, exit.entrance))?;
+        let (x, y) = (entrance.x, entrance.y);
+
+        let mut room = Room::load(self.assets, &exit.map, &data)?;
+        if let Some(states) = self.visited.get(&exit.map) {
+            for (skeleton, state) in room.skeletons.iter_mut().zip(states.iter()) {
+                skeleton.restore(*state);
+            }
+        }
+
+        let previous = mem::replace(&mut self.room, room);
+        let previousPath = mem::replace(&mut self.path, exit.map);
+        self.modified = modified;
+        self.visited.insert(previousPath, previous.skeletons.iter().map(|skeleton| skeleton.state()).collect());
+        //Free whatever only the old room was using
+        drop(previous);
+        self.assets.unloadUnused();
+
+        player.teleport(x, y);
+        Ok(true)
+    }
+
+    //Rebuilds the current room if its map file changed. The player stays where they are
+    //and the skeletons keep their state. Returns whether the room was reloaded.
+    pub fn reloadChanged(&mut self) -> Result<bool, String> {
+        let modified = AssetMod::modifiedTime(&self.path);
+        if modified == self.modified {
+            return Ok(false);
+        }
+        //Only try each version of the file once, a broken save shouldn't be reported every poll
+        self.modified = modified;
+
+        let data = MapLoader::load(&self.path)?;
+        let mut room = Room::load(self.assets, &self.path, &data)?;
+        for (skeleton, previous) in room.skeletons.iter_mut().zip(self.room.skeletons.iter()) {
+            skeleton.restore(previous.state());
+        }
+        self.room = room;
+        self.assets.unloadUnused();
+        Ok(true)
+    }
+}
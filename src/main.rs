@@ -2,104 +2,236 @@ extern crate sdl2;
 
 /*use sdl2::image::LoadTexture;
 use sdl2::rect::Rect;*/
+use sdl2::EventPump;
 use sdl2::hint;
-use sdl2::keyboard::KeyboardState;
-use sdl2::mixer::{self, Channel, DEFAULT_FORMAT, Music};
-use sdl2::pixels::Color;
+use sdl2::mixer::{self, Channel, Music, DEFAULT_FORMAT};
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::event::Event;
+use sdl2::surface::Surface;
 
+use std::env;
 /*use std::thread;
 use std::time::Duration;*/
 
+mod AnimationLoader;
+mod AssetMod;
+mod InputMod;
 mod PlayerMod;
 mod SpriteLoader;
 mod MapMod;
+mod MapLoader;
+mod TiledLoader;
 mod CollisionMod;
 mod SkeletonMod;
-
-use MapMod::*;
+mod CameraMod;
+mod WorldMod;
+mod PathfindingMod;
+mod RenderMod;
+
+use AssetMod::Assets;
+use CameraMod::Camera;
+use InputMod::{Action, Input};
 use PlayerMod::*;
-use SkeletonMod::*;
+use RenderMod::{RecordingRenderer, RenderQueue, Renderer};
+use WorldMod::World;
 
 const WIDTH: u32 = 850;
 const HEIGHT: u32 = 600;
 
 pub fn main() {
+    let args: Vec<String> = env::args().collect();
+    //--dev reloads images and maps when their files change
+    let devMode = args.iter().any(|arg| arg == "--dev");
+    //--headless [frames] runs the game without a window or sound, for machines with no display
+    let headless = args.iter().position(|arg| arg == "--headless")
+        .map(|i| args.get(i + 1).and_then(|frames| frames.parse().ok()).unwrap_or(HEADLESS_FRAMES));
+
+    if headless.is_some() {
+        env::set_var("SDL_AUDIODRIVER", "dummy");
+    }
+
     let context = sdl2::init().unwrap();
-    let videoSubsystem = context.video().unwrap();
     //let mixerContext = mixer::init(InitFlag::all());
 
     mixer::open_audio(44100, DEFAULT_FORMAT, 2, 1024).unwrap();
 
-    let music = Music::from_file(&"Resources/Music/hauntedhouseorgan.wav").unwrap();
-
-    music.play(-1).unwrap();
-
-    let mut channel = Channel::all();
-
     if !hint::set("SDL_RENDER_SCALE_QUALITY", "1") {
         eprintln!("Warning: Linear texture filtering may not be enabled.");
     }
 
     //let _imageContext = image::init(InitFlag::PNG);
 
-    let window = videoSubsystem.window("Halloween Demo", WIDTH, HEIGHT)
-        .position_centered()
-        .build()
-        .unwrap();
-    
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    let mut events = context.event_pump().unwrap();
+
+    let result = match headless {
+        Some(frames) => runHeadless(&mut events, frames, devMode).map(|renderer| {
+            println!("Ran {} frames, the last one drew {} things", renderer.frames(), renderer.lastFrame().len());
+        }),
+        None => {
+            let videoSubsystem = context.video().unwrap();
+            let window = videoSubsystem.window("Halloween Demo", WIDTH, HEIGHT)
+                .position_centered()
+                .build()
+                .unwrap();
+            
+            let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+
+            let creator = canvas.texture_creator();
+            run(&mut canvas, Assets::new(&creator), &mut events, None, devMode)
+        },
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+    }
+}
 
+//Plays the given number of frames, recording what they draw instead of showing it
+fn runHeadless(events: &mut EventPump, frames: u32, devMode: bool) -> Result<RecordingRenderer, String> {
+    //Textures still need a renderer to be made by, so use a software one that is never shown.
+    //The canvas owns the surface it draws to, so it has to outlive the textures.
+    let surface = Surface::new(WIDTH, HEIGHT, PixelFormatEnum::RGBA8888)?;
+    let canvas = surface.into_canvas()?;
     let creator = canvas.texture_creator();
+    let mut renderer = RecordingRenderer::new();
+    run(&mut renderer, Assets::new(&creator), events, Some(frames), devMode)?;
+    Ok(renderer)
+}
 
-    let mut player = Player::new(&creator, 50, 50);
+//Plays until the window is closed, or for the given number of frames
+fn run(renderer: &mut dyn Renderer, mut assets: Assets, events: &mut EventPump, frames: Option<u32>, devMode: bool) -> Result<(), String> {
+    assets.loadManifest(MANIFEST)?;
 
-    let mut skeleton = Skeleton::new(&creator, 600, 50);
+    let mut input = Input::load(CONTROLS)?;
 
-    let map = Map::new(TILES, &creator);
+    let music = assets.music(MUSIC)?;
 
-    canvas.set_draw_color(Color::RGB(0xff, 0x80, 0x00));
-    canvas.clear();
-    player.draw(&mut canvas);
-    canvas.present();
+    music.play(-1)?;
 
-    let mut events = context.event_pump().unwrap();
+    let mut channel = Channel::all();
+
+    let (mut world, (playerX, playerY)) = World::new(&assets, LEVEL)?;
+
+    let mut player = Player::new(&assets, playerX, playerY)?;
+
+    let mut camera = Camera::new(WIDTH, HEIGHT, world.room().map.pixelWidth(), world.room().map.pixelHeight());
+    camera.centerOn(player.position());
 
-    let mut state: Option<KeyboardState>;
+    let mut queue = RenderQueue::new();
 
-    let mut keyUpdate = false;
+    renderer.clear(BACKGROUND);
+    player.draw(&mut queue, &camera);
+    queue.flush(renderer);
+    renderer.present();
+
+    let mut reloadTimer = 0;
+    let mut frame = 0;
+    let mut paused = false;
  
     'main: loop {
+        if frames == Some(frame) {break 'main;}
+        frame += 1;
         for event in events.poll_iter() {
             if let Event::Quit{..} = event {break 'main;}
-            if let Event::KeyDown{..} | Event::KeyUp{..} = event {keyUpdate = true;}
         }
-        if keyUpdate {state = Some(events.keyboard_state());}
-        else {state = None}
-        channel = player.update(state, &events, channel, &map, &mut skeleton);
-        channel = skeleton.update(&player, channel);
-        canvas.clear();
-        map.render(&mut canvas);
-        skeleton.draw(&mut canvas);
-        player.draw(&mut canvas);
-        canvas.present();
-        keyUpdate = false;
+        input.update(&events.keyboard_state());
+        if input.wasPressed(Action::Pause) {
+            paused = !paused;
+            if paused {Music::pause();}
+            else {Music::resume();}
+        }
+        if !paused {
+            let room = world.room();
+            channel = player.update(&input, channel, &room.map, &mut room.skeletons)?;
+            for skeleton in room.skeletons.iter_mut() {
+                channel = skeleton.update(&player, &room.map, channel);
+            }
+            if world.checkExits(&mut player)? {
+                camera.setWorldSize(world.room().map.pixelWidth(), world.room().map.pixelHeight());
+                camera.centerOn(player.position());
+            }
+        }
+        reloadTimer += 1;
+        if reloadTimer >= RELOAD_INTERVAL {
+            reloadTimer = 0;
+            //Players can remap keys by editing the controls file while the game runs
+            match input.reloadChanged() {
+                Ok(true) => println!("Reloaded the controls"),
+                Ok(false) => (),
+                Err(e) => eprintln!("Error: {}", e),
+            }
+            if devMode {
+                let (reloaded, errors) = assets.reloadChanged();
+                for path in reloaded {println!("Reloaded {}", path);}
+                for e in errors {eprintln!("Error: {}", e);}
+                match world.reloadChanged() {
+                    Ok(true) => {
+                        println!("Reloaded the map");
+                        camera.setWorldSize(world.room().map.pixelWidth(), world.room().map.pixelHeight());
+                    },
+                    Ok(false) => (),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        }
+        camera.follow(player.position());
+        let room = world.room();
+        if !paused {room.map.update();}
+        room.map.render(&mut queue, &camera);
+        for skeleton in room.skeletons.iter() {
+            skeleton.draw(&mut queue, &camera);
+        }
+        player.draw(&mut queue, &camera);
+        renderer.clear(BACKGROUND);
+        queue.flush(renderer);
+        player.drawHealth(renderer);
+        renderer.present();
         //thread::sleep(Duration::from_nanos(16666667));
     }
 
+    Ok(())
 }
 
-const TILES: [[usize; 17]; 12] = [
-    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1],
-    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-];
+const LEVEL: &str = "Resources/Maps/Level1.map";
+const MUSIC: &str = "Resources/Music/hauntedhouseorgan.wav";
+const MANIFEST: &str = "Resources/Images/manifest.json";
+const CONTROLS: &str = "Resources/Controls.cfg";
+const RELOAD_INTERVAL: u32 = 30; //Ticks between checks for changed files in dev mode
+const HEADLESS_FRAMES: u32 = 600;
+const BACKGROUND: Color = Color{r: 0xff, g: 0x80, b: 0x00, a: 0xff};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use RenderMod::DrawCommand;
+
+    #[test]
+    fn headlessFramesAreRecorded() {
+        env::set_var("SDL_AUDIODRIVER", "dummy");
+        let context = sdl2::init().unwrap();
+        mixer::open_audio(44100, DEFAULT_FORMAT, 2, 1024).unwrap();
+        let mut events = context.event_pump().unwrap();
+
+        let renderer = runHeadless(&mut events, 3, false).unwrap();
+        //One frame is shown before the loop starts
+        assert_eq!(renderer.frames(), 4);
+
+        let frame = renderer.lastFrame();
+        match &frame[0] {
+            DrawCommand::Clear(color) => assert_eq!(*color, BACKGROUND),
+            _ => panic!("the frame should start by clearing the screen"),
+        }
+        let images: Vec<&str> = frame.iter().filter_map(|command| match command {
+            DrawCommand::Sprite{image, ..} => Some(image.as_str()),
+            _ => None,
+        }).collect();
+        //The ground goes beneath the ninja
+        let ninja = images.iter().position(|image| image.contains("Ninja")).unwrap();
+        assert!(images[..ninja].iter().any(|image| image.contains("Ground")));
+        //The health bar goes over everything
+        match frame.last() {
+            Some(DrawCommand::FillRect(..)) => (),
+            _ => panic!("the frame should end with the health bar"),
+        }
+    }
+}